@@ -0,0 +1,99 @@
+use std::future::Future;
+use std::time::{Duration, Instant};
+use sqlx::{Pool, Error};
+use sqlx::postgres::{Postgres, PgPoolOptions};
+use futures::TryStreamExt;
+use uuid::Uuid;
+use rmcs_auth_db::{Auth, ApiFilter, RoleFilter, UserFilter};
+use rmcs_auth_db::utility::generate_access_key;
+
+const PROCEDURES: usize = 300;
+const ROLES: usize = 10;
+const USERS: usize = 20;
+const ROUNDS: u32 = 20;
+
+async fn get_connection_pool() -> Result<Pool<Postgres>, Error>
+{
+    dotenvy::dotenv().ok();
+    let url = std::env::var("DATABASE_URL_AUTH_TEST").unwrap();
+    PgPoolOptions::new()
+        .max_connections(10)
+        .connect(&url)
+        .await
+}
+
+async fn truncate_tables(pool: &Pool<Postgres>) -> Result<(), Error>
+{
+    let sql = "TRUNCATE TABLE \"profile_user\", \"profile_role\", \"token\", \"group_role\", \"group_user\", \"group\", \"user_role\", \"user\", \"role_access_condition\", \"role_access\", \"role\", \"api_procedure\", \"api\";";
+    sqlx::query(sql)
+        .execute(pool)
+        .await?;
+    Ok(())
+}
+
+// api with many procedures, every role granted all procedures and every user assigned all roles
+async fn seed(auth: &Auth)
+{
+    let access_key = generate_access_key();
+    let api_id = auth.create_api(Uuid::new_v4(), "Resource", "localhost:9001", "RESOURCE", "", "Ap1_P4s5w0rd", &access_key).await.unwrap();
+    let mut proc_ids = Vec::new();
+    for i in 0..PROCEDURES {
+        proc_ids.push(auth.create_procedure(Uuid::new_v4(), api_id, &format!("Procedure{}", i), "").await.unwrap());
+    }
+    let mut role_ids = Vec::new();
+    for i in 0..ROLES {
+        let role_id = auth.create_role(Uuid::new_v4(), api_id, &format!("role{}", i), false, false, 900, 28800).await.unwrap();
+        for &proc_id in &proc_ids {
+            auth.add_role_access(role_id, proc_id).await.unwrap();
+        }
+        role_ids.push(role_id);
+    }
+    for i in 0..USERS {
+        let user_id = auth.create_user(Uuid::new_v4(), &format!("user{}", i), "", "", "Us3r_P4s5w0rd").await.unwrap();
+        for &role_id in &role_ids {
+            auth.add_user_role(user_id, role_id).await.unwrap();
+        }
+    }
+}
+
+// average duration of select with children fetched in a second query and of stream folding joined rows
+async fn compare<T, L, S>(name: &str, list: impl Fn() -> L, stream: impl Fn() -> S)
+where T: PartialEq + std::fmt::Debug, L: Future<Output = Vec<T>>, S: Future<Output = Vec<T>>
+{
+    assert_eq!(list().await, stream().await, "{} output differ", name);
+    let mut list_time = Duration::ZERO;
+    let mut stream_time = Duration::ZERO;
+    for _ in 0..ROUNDS {
+        let start = Instant::now();
+        list().await;
+        list_time += start.elapsed();
+        let start = Instant::now();
+        stream().await;
+        stream_time += start.elapsed();
+    }
+    println!("{:<6} batched: {:>10.3?}  joined: {:>10.3?}", name, list_time / ROUNDS, stream_time / ROUNDS);
+}
+
+#[tokio::main]
+async fn main()
+{
+    let pool = get_connection_pool().await.unwrap();
+    let auth = Auth::new_with_pool(pool);
+    truncate_tables(&auth.pool).await.unwrap();
+    seed(&auth).await;
+
+    compare("apis",
+        || async { auth.list_api(&ApiFilter::default()).await.unwrap() },
+        || async { auth.stream_apis(&ApiFilter::default()).try_collect().await.unwrap() }
+    ).await;
+    compare("roles",
+        || async { auth.list_role(&RoleFilter::default()).await.unwrap() },
+        || async { auth.stream_roles(&RoleFilter::default()).try_collect().await.unwrap() }
+    ).await;
+    compare("users",
+        || async { auth.list_user(&UserFilter::default()).await.unwrap() },
+        || async { auth.stream_users(&UserFilter::default()).try_collect().await.unwrap() }
+    ).await;
+
+    truncate_tables(&auth.pool).await.unwrap();
+}
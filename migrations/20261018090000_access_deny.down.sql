@@ -0,0 +1,2 @@
+ALTER TABLE "role_access"
+  DROP COLUMN IF EXISTS "deny";
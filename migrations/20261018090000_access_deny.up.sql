@@ -0,0 +1,2 @@
+ALTER TABLE "role_access"
+  ADD COLUMN IF NOT EXISTS "deny" boolean NOT NULL DEFAULT false;
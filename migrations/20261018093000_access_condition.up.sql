@@ -0,0 +1,13 @@
+CREATE TABLE IF NOT EXISTS "role_access_condition" (
+  "id" serial NOT NULL,
+  "role_id" uuid NOT NULL,
+  "procedure_id" uuid NOT NULL,
+  "profile" varchar(128) NOT NULL,
+  "operator" smallint NOT NULL DEFAULT 0,
+  "param" varchar(128),
+  "value" bytea[] NOT NULL DEFAULT '{}',
+  "type" smallint[] NOT NULL DEFAULT '{}',
+  PRIMARY KEY ("id"),
+  FOREIGN KEY ("role_id","procedure_id")
+    REFERENCES "role_access" ("role_id","procedure_id") ON UPDATE CASCADE ON DELETE CASCADE
+);
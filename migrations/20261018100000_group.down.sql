@@ -0,0 +1,3 @@
+DROP TABLE "group_role";
+DROP TABLE "group_user";
+DROP TABLE "group";
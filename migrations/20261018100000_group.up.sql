@@ -0,0 +1,27 @@
+CREATE TABLE IF NOT EXISTS "group" (
+  "group_id" uuid NOT NULL,
+  "name" varchar(128) NOT NULL,
+  "description" text NOT NULL,
+  PRIMARY KEY ("group_id"),
+  UNIQUE ("name")
+);
+
+CREATE TABLE IF NOT EXISTS "group_user" (
+  "group_id" uuid NOT NULL,
+  "user_id" uuid NOT NULL,
+  PRIMARY KEY ("group_id","user_id"),
+  FOREIGN KEY ("group_id")
+    REFERENCES "group" ("group_id"),
+  FOREIGN KEY ("user_id")
+    REFERENCES "user" ("user_id")
+);
+
+CREATE TABLE IF NOT EXISTS "group_role" (
+  "group_id" uuid NOT NULL,
+  "role_id" uuid NOT NULL,
+  PRIMARY KEY ("group_id","role_id"),
+  FOREIGN KEY ("group_id")
+    REFERENCES "group" ("group_id"),
+  FOREIGN KEY ("role_id")
+    REFERENCES "role" ("role_id")
+);
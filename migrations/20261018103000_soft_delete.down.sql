@@ -0,0 +1,11 @@
+ALTER TABLE "user"
+  DROP COLUMN IF EXISTS "disabled",
+  DROP COLUMN IF EXISTS "deleted_at";
+
+ALTER TABLE "role"
+  DROP COLUMN IF EXISTS "disabled",
+  DROP COLUMN IF EXISTS "deleted_at";
+
+ALTER TABLE "api"
+  DROP COLUMN IF EXISTS "disabled",
+  DROP COLUMN IF EXISTS "deleted_at";
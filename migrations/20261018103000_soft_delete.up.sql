@@ -0,0 +1,11 @@
+ALTER TABLE "api"
+  ADD COLUMN IF NOT EXISTS "disabled" boolean NOT NULL DEFAULT false,
+  ADD COLUMN IF NOT EXISTS "deleted_at" timestamptz;
+
+ALTER TABLE "role"
+  ADD COLUMN IF NOT EXISTS "disabled" boolean NOT NULL DEFAULT false,
+  ADD COLUMN IF NOT EXISTS "deleted_at" timestamptz;
+
+ALTER TABLE "user"
+  ADD COLUMN IF NOT EXISTS "disabled" boolean NOT NULL DEFAULT false,
+  ADD COLUMN IF NOT EXISTS "deleted_at" timestamptz;
@@ -0,0 +1,2 @@
+ALTER TABLE "token"
+  DROP CONSTRAINT IF EXISTS "token_user_id_fkey";
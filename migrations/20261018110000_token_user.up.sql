@@ -0,0 +1,6 @@
+DELETE FROM "token"
+  WHERE "user_id" NOT IN (SELECT "user_id" FROM "user");
+
+ALTER TABLE "token"
+  ADD CONSTRAINT "token_user_id_fkey" FOREIGN KEY ("user_id")
+    REFERENCES "user" ("user_id") ON UPDATE CASCADE ON DELETE CASCADE;
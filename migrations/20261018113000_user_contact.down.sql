@@ -0,0 +1,4 @@
+DROP INDEX IF EXISTS "user_phone_key";
+DROP INDEX IF EXISTS "user_email_key";
+DROP INDEX IF EXISTS "user_phone_idx";
+DROP INDEX IF EXISTS "user_email_idx";
@@ -0,0 +1,7 @@
+-- lookup indexes on normalized email and phone, uniqueness is opt-in with Auth::enable_unique_contact
+-- because existing rows may already hold duplicate contacts
+CREATE INDEX IF NOT EXISTS "user_email_idx" ON "user" (lower(trim("email")))
+  WHERE trim("email") <> '';
+
+CREATE INDEX IF NOT EXISTS "user_phone_idx" ON "user" (regexp_replace("phone", '[^0-9+]', '', 'g'))
+  WHERE regexp_replace("phone", '[^0-9+]', '', 'g') <> '';
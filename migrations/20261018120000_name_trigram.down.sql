@@ -0,0 +1,6 @@
+DROP INDEX IF EXISTS "group_name_trgm";
+DROP INDEX IF EXISTS "user_name_trgm";
+DROP INDEX IF EXISTS "role_name_trgm";
+DROP INDEX IF EXISTS "api_procedure_name_trgm";
+DROP INDEX IF EXISTS "api_category_trgm";
+DROP INDEX IF EXISTS "api_name_trgm";
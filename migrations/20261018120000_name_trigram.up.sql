@@ -0,0 +1,8 @@
+CREATE EXTENSION IF NOT EXISTS pg_trgm;
+
+CREATE INDEX IF NOT EXISTS "api_name_trgm" ON "api" USING gin ("name" gin_trgm_ops);
+CREATE INDEX IF NOT EXISTS "api_category_trgm" ON "api" USING gin ("category" gin_trgm_ops);
+CREATE INDEX IF NOT EXISTS "api_procedure_name_trgm" ON "api_procedure" USING gin ("name" gin_trgm_ops);
+CREATE INDEX IF NOT EXISTS "role_name_trgm" ON "role" USING gin ("name" gin_trgm_ops);
+CREATE INDEX IF NOT EXISTS "user_name_trgm" ON "user" USING gin ("name" gin_trgm_ops);
+CREATE INDEX IF NOT EXISTS "group_name_trgm" ON "group" USING gin ("name" gin_trgm_ops);
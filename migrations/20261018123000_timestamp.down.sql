@@ -0,0 +1,13 @@
+DROP INDEX IF EXISTS "profile_user_updated_at";
+DROP INDEX IF EXISTS "profile_role_updated_at";
+DROP INDEX IF EXISTS "user_updated_at";
+DROP INDEX IF EXISTS "role_updated_at";
+DROP INDEX IF EXISTS "api_procedure_updated_at";
+DROP INDEX IF EXISTS "api_updated_at";
+
+ALTER TABLE "profile_user" DROP COLUMN "updated_at", DROP COLUMN "created_at";
+ALTER TABLE "profile_role" DROP COLUMN "updated_at", DROP COLUMN "created_at";
+ALTER TABLE "user" DROP COLUMN "updated_at", DROP COLUMN "created_at";
+ALTER TABLE "role" DROP COLUMN "updated_at", DROP COLUMN "created_at";
+ALTER TABLE "api_procedure" DROP COLUMN "updated_at", DROP COLUMN "created_at";
+ALTER TABLE "api" DROP COLUMN "updated_at", DROP COLUMN "created_at";
@@ -0,0 +1,30 @@
+ALTER TABLE "api"
+  ADD COLUMN "created_at" timestamptz NOT NULL DEFAULT CURRENT_TIMESTAMP,
+  ADD COLUMN "updated_at" timestamptz NOT NULL DEFAULT CURRENT_TIMESTAMP;
+
+ALTER TABLE "api_procedure"
+  ADD COLUMN "created_at" timestamptz NOT NULL DEFAULT CURRENT_TIMESTAMP,
+  ADD COLUMN "updated_at" timestamptz NOT NULL DEFAULT CURRENT_TIMESTAMP;
+
+ALTER TABLE "role"
+  ADD COLUMN "created_at" timestamptz NOT NULL DEFAULT CURRENT_TIMESTAMP,
+  ADD COLUMN "updated_at" timestamptz NOT NULL DEFAULT CURRENT_TIMESTAMP;
+
+ALTER TABLE "user"
+  ADD COLUMN "created_at" timestamptz NOT NULL DEFAULT CURRENT_TIMESTAMP,
+  ADD COLUMN "updated_at" timestamptz NOT NULL DEFAULT CURRENT_TIMESTAMP;
+
+ALTER TABLE "profile_role"
+  ADD COLUMN "created_at" timestamptz NOT NULL DEFAULT CURRENT_TIMESTAMP,
+  ADD COLUMN "updated_at" timestamptz NOT NULL DEFAULT CURRENT_TIMESTAMP;
+
+ALTER TABLE "profile_user"
+  ADD COLUMN "created_at" timestamptz NOT NULL DEFAULT CURRENT_TIMESTAMP,
+  ADD COLUMN "updated_at" timestamptz NOT NULL DEFAULT CURRENT_TIMESTAMP;
+
+CREATE INDEX IF NOT EXISTS "api_updated_at" ON "api" ("updated_at");
+CREATE INDEX IF NOT EXISTS "api_procedure_updated_at" ON "api_procedure" ("updated_at");
+CREATE INDEX IF NOT EXISTS "role_updated_at" ON "role" ("updated_at");
+CREATE INDEX IF NOT EXISTS "user_updated_at" ON "user" ("updated_at");
+CREATE INDEX IF NOT EXISTS "profile_role_updated_at" ON "profile_role" ("updated_at");
+CREATE INDEX IF NOT EXISTS "profile_user_updated_at" ON "profile_user" ("updated_at");
@@ -0,0 +1,4 @@
+ALTER TABLE "user" DROP COLUMN "version";
+ALTER TABLE "role" DROP COLUMN "version";
+ALTER TABLE "api_procedure" DROP COLUMN "version";
+ALTER TABLE "api" DROP COLUMN "version";
@@ -0,0 +1,11 @@
+ALTER TABLE "api"
+  ADD COLUMN "version" integer NOT NULL DEFAULT 0;
+
+ALTER TABLE "api_procedure"
+  ADD COLUMN "version" integer NOT NULL DEFAULT 0;
+
+ALTER TABLE "role"
+  ADD COLUMN "version" integer NOT NULL DEFAULT 0;
+
+ALTER TABLE "user"
+  ADD COLUMN "version" integer NOT NULL DEFAULT 0;
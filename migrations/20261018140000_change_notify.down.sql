@@ -0,0 +1,9 @@
+DROP TRIGGER "group_role_change_notify" ON "group_role";
+DROP TRIGGER "group_user_change_notify" ON "group_user";
+DROP TRIGGER "token_change_notify" ON "token";
+DROP TRIGGER "user_role_change_notify" ON "user_role";
+DROP TRIGGER "role_access_change_notify" ON "role_access";
+DROP TRIGGER "role_change_notify" ON "role";
+DROP TRIGGER "api_procedure_change_notify" ON "api_procedure";
+DROP TRIGGER "api_change_notify" ON "api";
+DROP FUNCTION "notify_auth_change"();
@@ -0,0 +1,37 @@
+CREATE OR REPLACE FUNCTION "notify_auth_change"() RETURNS trigger AS $$
+DECLARE
+  "record" jsonb;
+BEGIN
+  IF TG_OP = 'DELETE' THEN
+    "record" := to_jsonb(OLD);
+  ELSE
+    "record" := to_jsonb(NEW);
+  END IF;
+  PERFORM pg_notify('auth_change', concat_ws(':', TG_TABLE_NAME, TG_OP, "record" ->> TG_ARGV[0], "record" ->> TG_ARGV[1]));
+  RETURN NULL;
+END;
+$$ LANGUAGE plpgsql;
+
+CREATE TRIGGER "api_change_notify" AFTER INSERT OR UPDATE OR DELETE ON "api"
+  FOR EACH ROW EXECUTE FUNCTION "notify_auth_change"('api_id');
+
+CREATE TRIGGER "api_procedure_change_notify" AFTER INSERT OR UPDATE OR DELETE ON "api_procedure"
+  FOR EACH ROW EXECUTE FUNCTION "notify_auth_change"('procedure_id', 'api_id');
+
+CREATE TRIGGER "role_change_notify" AFTER INSERT OR UPDATE OR DELETE ON "role"
+  FOR EACH ROW EXECUTE FUNCTION "notify_auth_change"('role_id', 'api_id');
+
+CREATE TRIGGER "role_access_change_notify" AFTER INSERT OR UPDATE OR DELETE ON "role_access"
+  FOR EACH ROW EXECUTE FUNCTION "notify_auth_change"('role_id', 'procedure_id');
+
+CREATE TRIGGER "user_role_change_notify" AFTER INSERT OR UPDATE OR DELETE ON "user_role"
+  FOR EACH ROW EXECUTE FUNCTION "notify_auth_change"('user_id', 'role_id');
+
+CREATE TRIGGER "token_change_notify" AFTER INSERT OR UPDATE OR DELETE ON "token"
+  FOR EACH ROW EXECUTE FUNCTION "notify_auth_change"('access_id', 'user_id');
+
+CREATE TRIGGER "group_user_change_notify" AFTER INSERT OR UPDATE OR DELETE ON "group_user"
+  FOR EACH ROW EXECUTE FUNCTION "notify_auth_change"('group_id', 'user_id');
+
+CREATE TRIGGER "group_role_change_notify" AFTER INSERT OR UPDATE OR DELETE ON "group_role"
+  FOR EACH ROW EXECUTE FUNCTION "notify_auth_change"('group_id', 'role_id');
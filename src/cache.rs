@@ -0,0 +1,182 @@
+use std::collections::{HashMap, BTreeSet};
+use std::hash::Hash;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+use uuid::Uuid;
+
+use crate::schema::api::ApiSchema;
+use crate::schema::auth_role::RoleSchema;
+use crate::schema::auth_user::UserSchema;
+
+// map of cached values which expire after ttl and hold at most capacity entries
+// keys are also ordered by insert time so expired and oldest entries are found without a full scan
+#[derive(Debug)]
+struct TtlMap<K, V> {
+    entries: HashMap<K, (V, Instant)>,
+    order: BTreeSet<(Instant, K)>
+}
+
+impl<K: Eq + Hash + Ord + Copy, V: Clone> TtlMap<K, V> {
+
+    fn new() -> Self {
+        TtlMap { entries: HashMap::new(), order: BTreeSet::new() }
+    }
+
+    fn get(&mut self, key: &K, ttl: Duration) -> Option<V> {
+        match self.entries.get(key) {
+            Some((value, inserted)) if inserted.elapsed() < ttl => Some(value.clone()),
+            Some(_) => {
+                self.remove(key);
+                None
+            },
+            None => None
+        }
+    }
+
+    fn insert(&mut self, key: K, value: V, ttl: Duration, capacity: usize) {
+        if capacity == 0 {
+            return;
+        }
+        self.remove(&key);
+        // drop expired entries first then the oldest entry when cache still full
+        while let Some(&(inserted, k)) = self.order.first() {
+            if inserted.elapsed() < ttl && self.entries.len() < capacity {
+                break;
+            }
+            self.remove(&k);
+        }
+        let inserted = Instant::now();
+        self.entries.insert(key, (value, inserted));
+        self.order.insert((inserted, key));
+    }
+
+    fn remove(&mut self, key: &K) {
+        if let Some((_, inserted)) = self.entries.remove(key) {
+            self.order.remove(&(inserted, *key));
+        }
+    }
+
+    fn retain(&mut self, f: impl Fn(&K, &V) -> bool) {
+        let order = &mut self.order;
+        self.entries.retain(|k, (v, inserted)| {
+            let keep = f(k, v);
+            if !keep {
+                order.remove(&(*inserted, *k));
+            }
+            keep
+        });
+    }
+
+    fn clear(&mut self) {
+        self.entries.clear();
+        self.order.clear();
+    }
+
+}
+
+#[derive(Debug)]
+struct CacheMaps {
+    apis: TtlMap<Uuid, ApiSchema>,
+    roles: TtlMap<Uuid, RoleSchema>,
+    users: TtlMap<Uuid, UserSchema>,
+    access: TtlMap<(Uuid, Uuid), bool>
+}
+
+// read cache of api, role, user with its roles and access decision shared by clones of Auth
+#[derive(Debug)]
+pub(crate) struct AuthCache {
+    ttl: Duration,
+    capacity: usize,
+    maps: Mutex<CacheMaps>
+}
+
+impl AuthCache {
+
+    pub(crate) fn new(ttl: Duration, capacity: usize) -> Self {
+        AuthCache {
+            ttl,
+            capacity,
+            maps: Mutex::new(CacheMaps {
+                apis: TtlMap::new(),
+                roles: TtlMap::new(),
+                users: TtlMap::new(),
+                access: TtlMap::new()
+            })
+        }
+    }
+
+    fn maps(&self) -> std::sync::MutexGuard<'_, CacheMaps> {
+        // cached values are plain data so a poisoned lock is still usable
+        self.maps.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    pub(crate) fn get_api(&self, id: Uuid) -> Option<ApiSchema> {
+        self.maps().apis.get(&id, self.ttl)
+    }
+
+    pub(crate) fn put_api(&self, api: &ApiSchema) {
+        self.maps().apis.insert(api.id, api.clone(), self.ttl, self.capacity);
+    }
+
+    pub(crate) fn get_role(&self, id: Uuid) -> Option<RoleSchema> {
+        self.maps().roles.get(&id, self.ttl)
+    }
+
+    pub(crate) fn put_role(&self, role: &RoleSchema) {
+        self.maps().roles.insert(role.id, role.clone(), self.ttl, self.capacity);
+    }
+
+    pub(crate) fn get_user(&self, id: Uuid) -> Option<UserSchema> {
+        self.maps().users.get(&id, self.ttl)
+    }
+
+    pub(crate) fn put_user(&self, user: &UserSchema) {
+        self.maps().users.insert(user.id, user.clone(), self.ttl, self.capacity);
+    }
+
+    pub(crate) fn get_access(&self, user_id: Uuid, procedure_id: Uuid) -> Option<bool> {
+        self.maps().access.get(&(user_id, procedure_id), self.ttl)
+    }
+
+    pub(crate) fn put_access(&self, user_id: Uuid, procedure_id: Uuid, access: bool) {
+        self.maps().access.insert((user_id, procedure_id), access, self.ttl, self.capacity);
+    }
+
+    // remove a user and its access decisions, used when only the user or its roles changed
+    pub(crate) fn remove_user(&self, id: Uuid) {
+        let mut maps = self.maps();
+        maps.users.remove(&id);
+        maps.access.retain(|(user_id, _), _| *user_id != id);
+    }
+
+    // api and role changes propagate to users role set and access decisions so those are cleared too
+    // roles of the api are removed because api state and cascade delete apply to its roles
+    pub(crate) fn remove_api(&self, id: Uuid) {
+        let mut maps = self.maps();
+        maps.apis.remove(&id);
+        maps.roles.retain(|_, role| role.api_id != id);
+        maps.users.clear();
+        maps.access.clear();
+    }
+
+    pub(crate) fn remove_role(&self, id: Uuid) {
+        let mut maps = self.maps();
+        maps.roles.remove(&id);
+        maps.apis.clear();
+        maps.users.clear();
+        maps.access.clear();
+    }
+
+    pub(crate) fn clear_access(&self) {
+        self.maps().access.clear();
+    }
+
+    pub(crate) fn clear(&self) {
+        let mut maps = self.maps();
+        maps.apis.clear();
+        maps.roles.clear();
+        maps.users.clear();
+        maps.access.clear();
+    }
+
+}
@@ -0,0 +1,84 @@
+use std::fmt;
+use sqlx::postgres::PgDatabaseError;
+
+#[derive(Debug)]
+pub enum AuthError {
+    NotFound { entity: String },
+    Duplicate { field: String },
+    Referenced { by: String },
+    Conflict { entity: String, expected: i32, actual: i32 },
+    Validation(String),
+    Hashing,
+    Database(sqlx::Error)
+}
+
+impl AuthError {
+    pub(crate) fn not_found(entity: &str) -> Self {
+        AuthError::NotFound { entity: entity.to_owned() }
+    }
+}
+
+impl fmt::Display for AuthError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            AuthError::NotFound { entity } => write!(f, "{} not found", entity),
+            AuthError::Duplicate { field } => write!(f, "duplicate value of {}", field),
+            AuthError::Referenced { by } => write!(f, "still referenced by {}", by),
+            AuthError::Conflict { entity, expected, actual } =>
+                write!(f, "{} version conflict, expected version {} but found {}", entity, expected, actual),
+            AuthError::Validation(message) => write!(f, "invalid value: {}", message),
+            AuthError::Hashing => write!(f, "failed to hash password"),
+            AuthError::Database(error) => write!(f, "{}", error)
+        }
+    }
+}
+
+impl std::error::Error for AuthError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            AuthError::Database(error) => Some(error),
+            _ => None
+        }
+    }
+}
+
+impl From<sqlx::Error> for AuthError {
+    fn from(value: sqlx::Error) -> Self {
+        let mapped = value.as_database_error()
+            .and_then(|e| e.try_downcast_ref::<PgDatabaseError>())
+            .and_then(|e| match e.code() {
+                // unique violation
+                "23505" => Some(AuthError::Duplicate { field: constraint_field(e.table(), e.constraint()) }),
+                // foreign key violation, missing parent on insert or existing child on delete
+                "23503" => match e.detail().and_then(missing_table) {
+                    Some(table) => Some(AuthError::not_found(table)),
+                    None => Some(AuthError::Referenced { by: e.table().unwrap_or_default().to_owned() })
+                },
+                // not null and check violation
+                "23502" | "23514" => Some(AuthError::Validation(e.message().to_owned())),
+                _ => None
+            });
+        mapped.unwrap_or(AuthError::Database(value))
+    }
+}
+
+// get column name from unique constraint name with format <table>_<column>_key or <table>_pkey
+fn constraint_field(table: Option<&str>, constraint: Option<&str>) -> String
+{
+    let constraint = constraint.unwrap_or_default();
+    let name = table
+        .and_then(|t| constraint.strip_prefix(t))
+        .and_then(|c| c.strip_prefix('_'))
+        .unwrap_or(constraint);
+    match name {
+        "pkey" => String::from("id"),
+        _ => name.strip_suffix("_key").unwrap_or(name).to_owned()
+    }
+}
+
+// get referenced table name from detail message: Key (column)=(value) is not present in table "table".
+fn missing_table(detail: &str) -> Option<&str>
+{
+    let (_, table) = detail.split_once("is not present in table \"")?;
+    table.split('"').next()
+}
@@ -1,8 +1,16 @@
 pub mod schema;
-pub(crate) mod operation;
+pub mod operation;
 pub mod utility;
-
-use sqlx::{Pool, Error};
+pub mod error;
+pub(crate) mod transaction;
+pub(crate) mod cache;
+
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::Duration;
+use sqlx::Pool;
+use futures::Stream;
+use async_stream::{stream, try_stream};
 use sqlx::postgres::{Postgres, PgPoolOptions};
 use sqlx::types::chrono::{DateTime, Utc};
 use uuid::Uuid;
@@ -12,25 +20,50 @@ use operation::role;
 use operation::user;
 use operation::profile;
 use operation::token;
+use operation::access;
+use operation::group;
+use operation::cascade;
+use operation::change;
 pub use schema::api::{ApiSchema, ProcedureSchema};
-pub use schema::auth_role::RoleSchema;
-pub use schema::auth_user::UserSchema;
+pub use schema::auth_role::{RoleSchema, AccessConditionSchema, ConditionOperator};
+pub use schema::auth_user::{UserSchema, UserRoleSchema, DuplicateContactSchema};
 pub use schema::auth_token::TokenSchema;
+pub use schema::auth_group::GroupSchema;
 pub use schema::profile::{RoleProfileSchema, UserProfileSchema, ProfileMode};
+pub use schema::permission::{PermissionSchema, PermissionMatrix};
+pub use schema::cascade::DeleteSummary;
+pub use schema::page::{Page, PageOrder};
+pub use schema::secret::Secret;
+pub use schema::change::{ChangeEvent, ChangeKind};
+pub use error::AuthError;
+pub use transaction::{AuthTx, AuthExecutor, TxConn};
+pub use api::ApiFilter;
+pub use role::RoleFilter;
+pub use user::UserFilter;
 use token::TokenSelector;
+use schema::page::Keyset;
+use cache::AuthCache;
 use rmcs_resource_db::schema::value::{DataValue, DataType};
 
+// Auth over a connection pool by default, AuthTx runs the same methods over a transaction
 #[derive(Debug, Clone)]
-pub struct Auth {
-    pub pool: Pool<Postgres>,
-    options: AuthOptions
+pub struct Auth<E = Pool<Postgres>> {
+    pub pool: E,
+    options: AuthOptions,
+    cache: Option<Arc<AuthCache>>
 }
 
 #[derive(Debug, Clone)]
 pub struct AuthOptions {
     limit: u32,
+    offset: u32,
     with_description: bool,
-    order: Vec<OrderOption>
+    with_inactive: bool,
+    with_secrets: bool,
+    with_profiles: bool,
+    search: SearchOption,
+    order: Vec<OrderOption>,
+    keyset: Option<Keyset>
 }
 
 #[derive(Debug, Clone)]
@@ -41,16 +74,73 @@ pub enum OrderOption {
     NameDesc
 }
 
+#[derive(Debug, Clone, Default)]
+pub enum SearchOption {
+    #[default]
+    Contains,
+    Prefix
+}
+
 impl Default for AuthOptions {
     fn default() -> Self {
         AuthOptions { 
             limit: 10000, 
-            with_description: false, 
-            order: vec![] 
+            offset: 0, 
+            with_description: true, 
+            with_inactive: false, 
+            with_secrets: false,
+            with_profiles: false,
+            search: SearchOption::Contains,
+            order: vec![],
+            keyset: None
         }
     }
 }
 
+impl AuthOptions {
+
+    pub fn limit(mut self, limit: u32) -> Self {
+        self.limit = limit;
+        self
+    }
+
+    pub fn offset(mut self, offset: u32) -> Self {
+        self.offset = offset;
+        self
+    }
+
+    pub fn with_description(mut self, with_description: bool) -> Self {
+        self.with_description = with_description;
+        self
+    }
+
+    pub fn with_inactive(mut self, with_inactive: bool) -> Self {
+        self.with_inactive = with_inactive;
+        self
+    }
+
+    pub fn with_secrets(mut self, with_secrets: bool) -> Self {
+        self.with_secrets = with_secrets;
+        self
+    }
+
+    pub fn with_profiles(mut self, with_profiles: bool) -> Self {
+        self.with_profiles = with_profiles;
+        self
+    }
+
+    pub fn search(mut self, search: SearchOption) -> Self {
+        self.search = search;
+        self
+    }
+
+    pub fn order(mut self, order: Vec<OrderOption>) -> Self {
+        self.order = order;
+        self
+    }
+
+}
+
 impl Auth {
 
     pub async fn new(host: &str, username: &str, password: &str, database: &str) -> Auth {
@@ -66,470 +156,1400 @@ impl Auth {
             .expect(&format!("Error connecting to {}", url));
         Auth {
             pool,
-            options: AuthOptions::default()
+            options: AuthOptions::default(),
+            cache: None
         }
     }
 
     pub fn new_with_pool(pool: Pool<Postgres>) -> Auth {
         Auth {
             pool,
-            options: AuthOptions::default()
+            options: AuthOptions::default(),
+            cache: None
+        }
+    }
+
+    pub fn with_options(&self, options: AuthOptions) -> Auth {
+        Auth {
+            pool: self.pool.clone(),
+            options,
+            cache: self.cache.clone()
+        }
+    }
+
+    // cache is shared with clones of this Auth and with Auth created by with_options
+    pub fn with_cache(&self, ttl: Duration, capacity: usize) -> Auth {
+        Auth {
+            pool: self.pool.clone(),
+            options: self.options.clone(),
+            cache: Some(Arc::new(AuthCache::new(ttl, capacity)))
+        }
+    }
+
+    pub fn set_cache(&mut self, ttl: Duration, capacity: usize) {
+        self.cache = Some(Arc::new(AuthCache::new(ttl, capacity)));
+    }
+
+    pub async fn begin(&self) -> Result<AuthTx, AuthError> {
+        let tx = self.pool.begin().await?;
+        Ok(AuthTx::new(tx, self.options.clone(), self.cache.clone()))
+    }
+
+    // cache of this Auth is invalidated by each received change before the change is yielded
+    pub async fn subscribe_changes(&self)
+        -> Result<impl Stream<Item = ChangeEvent> + Send + use<>, AuthError>
+    {
+        let changes = change::listen_change(&self.pool).await?;
+        let pool = self.pool.clone();
+        let cache = self.cache.clone();
+        Ok(stream! {
+            for await event in changes {
+                if let Some(cache) = &cache {
+                    change::invalidate_change(&pool, cache, &event).await;
+                }
+                yield event;
+            }
+        })
+    }
+
+}
+
+impl<E: AuthExecutor> Auth<E> {
+
+    pub(crate) fn with_executor(pool: E, options: AuthOptions) -> Auth<E> {
+        Auth {
+            pool,
+            options,
+            cache: None
         }
     }
 
-    pub fn set_limit(mut self, limit: u32) {
+    pub fn options(&self) -> &AuthOptions {
+        &self.options
+    }
+
+    pub fn set_options(&mut self, options: AuthOptions) {
+        self.options = options;
+    }
+
+    pub fn set_limit(&mut self, limit: u32) {
         self.options.limit = limit;
     }
 
-    pub fn set_with_description(mut self, with_description: bool) {
+    pub fn set_offset(&mut self, offset: u32) {
+        self.options.offset = offset;
+    }
+
+    pub fn set_with_description(&mut self, with_description: bool) {
         self.options.with_description = with_description;
     }
 
-    pub fn set_order(mut self, order: Vec<OrderOption>) {
+    pub fn set_order(&mut self, order: Vec<OrderOption>) {
         self.options.order = order;
     }
 
+    pub fn set_with_inactive(&mut self, with_inactive: bool) {
+        self.options.with_inactive = with_inactive;
+    }
+
+    pub fn set_with_secrets(&mut self, with_secrets: bool) {
+        self.options.with_secrets = with_secrets;
+    }
+
+    pub fn set_with_profiles(&mut self, with_profiles: bool) {
+        self.options.with_profiles = with_profiles;
+    }
+
+    pub fn set_search(&mut self, search: SearchOption) {
+        self.options.search = search;
+    }
+
+    pub fn invalidate_cache(&self) {
+        if let Some(cache) = &self.cache {
+            cache.clear();
+        }
+    }
+
+    pub fn invalidate_api(&self, id: Uuid) {
+        if let Some(cache) = &self.cache {
+            cache.remove_api(id);
+        }
+    }
+
+    pub fn invalidate_role(&self, id: Uuid) {
+        if let Some(cache) = &self.cache {
+            cache.remove_role(id);
+        }
+    }
+
+    pub fn invalidate_user(&self, id: Uuid) {
+        if let Some(cache) = &self.cache {
+            cache.remove_user(id);
+        }
+    }
+
+    fn invalidate_access(&self) {
+        if let Some(cache) = &self.cache {
+            cache.clear_access();
+        }
+    }
+
+    fn search_pattern(&self, value: &str) -> String {
+        utility::like_pattern(value, matches!(self.options.search, SearchOption::Prefix))
+    }
+
+    fn page_options(&self, order: PageOrder, size: u32, cursor: Option<&str>) -> Result<AuthOptions, AuthError> {
+        let mut options = self.options.clone();
+        options.keyset = Some(Keyset::new(order, size, cursor)?);
+        Ok(options)
+    }
+
+    fn api_option_filter(&self, name: Option<&str>, category: Option<&str>) -> ApiFilter {
+        let mut filter = ApiFilter::default();
+        if let Some(name) = name {
+            filter = filter.name_like(&self.search_pattern(name));
+        }
+        if let Some(category) = category {
+            filter = filter.category_like(&self.search_pattern(category));
+        }
+        filter
+    }
+
+    fn role_option_filter(&self, api_id: Option<Uuid>, user_id: Option<Uuid>, name: Option<&str>) -> RoleFilter {
+        let mut filter = RoleFilter::default();
+        if let Some(api_id) = api_id {
+            filter = filter.api_id(api_id);
+        }
+        if let Some(user_id) = user_id {
+            filter = filter.user_id(user_id);
+        }
+        if let Some(name) = name {
+            filter = filter.name_like(&self.search_pattern(name));
+        }
+        filter
+    }
+
+    fn user_option_filter(&self, api_id: Option<Uuid>, role_id: Option<Uuid>, name: Option<&str>) -> UserFilter {
+        let mut filter = UserFilter::default();
+        if let Some(api_id) = api_id {
+            filter = filter.api_id(api_id);
+        }
+        if let Some(role_id) = role_id {
+            filter = filter.has_role(role_id);
+        }
+        if let Some(name) = name {
+            filter = filter.name_like(&self.search_pattern(name));
+        }
+        filter
+    }
+
     pub async fn read_api(&self, id: Uuid)
-        -> Result<ApiSchema, Error>
+        -> Result<ApiSchema, AuthError>
     {
-        api::select_api(&self.pool, Some(id), None, None, None, None).await?
-        .into_iter().next().ok_or(Error::RowNotFound)
+        if let Some(api) = self.cache.as_ref().and_then(|c| c.get_api(id)) {
+            return Ok(api);
+        }
+        api::select_api(&mut *self.pool.conn().await?, &ApiFilter::default().ids(&[id]), true, None).await?
+        .into_iter().next().ok_or(AuthError::not_found("api"))
+        .inspect(|api| if let Some(cache) = &self.cache { cache.put_api(api) })
     }
 
     pub async fn read_api_by_name(&self, name: &str)
-        -> Result<ApiSchema, Error>
+        -> Result<ApiSchema, AuthError>
+    {
+        api::select_api(&mut *self.pool.conn().await?, &ApiFilter::default().names(&[name]), true, None).await?
+        .into_iter().next().ok_or(AuthError::not_found("api"))
+    }
+
+    pub async fn read_api_with_secrets(&self, id: Uuid)
+        -> Result<ApiSchema, AuthError>
     {
-        api::select_api(&self.pool, None, None, Some(name), None, None).await?
-        .into_iter().next().ok_or(Error::RowNotFound)
+        let options = AuthOptions::default().with_secrets(true);
+        api::select_api(&mut *self.pool.conn().await?, &ApiFilter::default().ids(&[id]), true, Some(&options)).await?
+        .into_iter().next().ok_or(AuthError::not_found("api"))
+    }
+
+    pub async fn read_api_by_name_with_secrets(&self, name: &str)
+        -> Result<ApiSchema, AuthError>
+    {
+        let options = AuthOptions::default().with_secrets(true);
+        api::select_api(&mut *self.pool.conn().await?, &ApiFilter::default().names(&[name]), true, Some(&options)).await?
+        .into_iter().next().ok_or(AuthError::not_found("api"))
+    }
+
+    pub async fn list_api(&self, filter: &ApiFilter)
+        -> Result<Vec<ApiSchema>, AuthError>
+    {
+        api::select_api(&mut *self.pool.conn().await?, filter, self.options.with_inactive, Some(&self.options))
+        .await
+    }
+
+    pub async fn count_api(&self, filter: &ApiFilter)
+        -> Result<u64, AuthError>
+    {
+        api::count_api(&mut *self.pool.conn().await?, filter, self.options.with_inactive)
+        .await
     }
 
     pub async fn list_api_by_ids(&self, ids: &[Uuid])
-        -> Result<Vec<ApiSchema>, Error>
+        -> Result<Vec<ApiSchema>, AuthError>
     {
-        api::select_api(&self.pool, None, Some(ids), None, None, None)
+        api::select_api(&mut *self.pool.conn().await?, &ApiFilter::default().ids(ids), self.options.with_inactive, Some(&self.options))
         .await
     }
 
     pub async fn list_api_by_name(&self, name: &str)
-        -> Result<Vec<ApiSchema>, Error>
+        -> Result<Vec<ApiSchema>, AuthError>
     {
-        api::select_api(&self.pool, None, None, None, Some(name), None)
+        let name_like = self.search_pattern(name);
+        api::select_api(&mut *self.pool.conn().await?, &ApiFilter::default().name_like(&name_like), self.options.with_inactive, Some(&self.options))
         .await
     }
 
     pub async fn list_api_by_category(&self, category: &str)
-        -> Result<Vec<ApiSchema>, Error>
+        -> Result<Vec<ApiSchema>, AuthError>
     {
-        api::select_api(&self.pool, None, None, None, None, Some(category))
+        let category_like = self.search_pattern(category);
+        api::select_api(&mut *self.pool.conn().await?, &ApiFilter::default().category_like(&category_like), self.options.with_inactive, Some(&self.options))
         .await
     }
 
     pub async fn list_api_option(&self, name: Option<&str>, category: Option<&str>)
-        -> Result<Vec<ApiSchema>, Error>
+        -> Result<Vec<ApiSchema>, AuthError>
     {
-        api::select_api(&self.pool, None, None, None, name, category)
+        api::select_api(&mut *self.pool.conn().await?, &self.api_option_filter(name, category), self.options.with_inactive, Some(&self.options))
         .await
     }
 
+    pub async fn count_api_option(&self, name: Option<&str>, category: Option<&str>)
+        -> Result<u64, AuthError>
+    {
+        api::count_api(&mut *self.pool.conn().await?, &self.api_option_filter(name, category), self.options.with_inactive)
+        .await
+    }
+
+    pub async fn list_api_page(&self, name: Option<&str>, category: Option<&str>, order: PageOrder, size: u32, cursor: Option<&str>)
+        -> Result<Page<ApiSchema>, AuthError>
+    {
+        let options = self.page_options(order, size, cursor)?;
+        let apis = api::select_api(&mut *self.pool.conn().await?, &self.api_option_filter(name, category), self.options.with_inactive, Some(&options))
+            .await?;
+        Ok(Page::new(apis, size, |a| (a.name.as_str(), a.id)))
+    }
+
+    pub async fn list_api_updated_since(&self, timestamp: DateTime<Utc>)
+        -> Result<Vec<ApiSchema>, AuthError>
+    {
+        api::select_api(&mut *self.pool.conn().await?, &ApiFilter::default().updated_since(timestamp), true, Some(&self.options))
+        .await
+    }
+
+    pub fn stream_apis<'a>(&'a self, filter: &ApiFilter)
+        -> impl Stream<Item = Result<ApiSchema, AuthError>> + Send + use<'a, E>
+    {
+        // connection is acquired on first poll so the filter is cloned into the stream
+        let filter = filter.clone();
+        try_stream! {
+            let mut conn = self.pool.conn().await?;
+            for await api in api::stream_api(&mut *conn, &filter, self.options.with_inactive) {
+                yield api?;
+            }
+        }
+    }
+
     pub async fn create_api(&self, id: Uuid, name: &str, address: &str, category: &str, description: &str, password: &str, access_key: &[u8])
-        -> Result<Uuid, Error>
+        -> Result<Uuid, AuthError>
     {
-        api::insert_api(&self.pool, id, name, address, category, description, password, access_key)
+        api::insert_api(&mut *self.pool.conn().await?, id, name, address, category, description, password, access_key)
         .await
     }
 
     pub async fn update_api(&self, id: Uuid, name: Option<&str>, address: Option<&str>, category: Option<&str>, description: Option<&str>, password: Option<&str>, access_key: Option<&[u8]>)
-        -> Result<(), Error>
+        -> Result<(), AuthError>
+    {
+        api::update_api(&mut *self.pool.conn().await?, id, name, address, category, description, password, access_key, None)
+        .await.map(|_| ())
+        .inspect(|_| self.invalidate_api(id))
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    pub async fn update_api_versioned(&self, id: Uuid, version: i32, name: Option<&str>, address: Option<&str>, category: Option<&str>, description: Option<&str>, password: Option<&str>, access_key: Option<&[u8]>)
+        -> Result<i32, AuthError>
+    {
+        let rows = api::update_api(&mut *self.pool.conn().await?, id, name, address, category, description, password, access_key, Some(version)).await?;
+        self.invalidate_api(id);
+        if rows == 0 {
+            let api = self.read_api(id).await?;
+            return Err(AuthError::Conflict { entity: String::from("api"), expected: version, actual: api.version });
+        }
+        Ok(version + 1)
+    }
+
+    pub async fn disable_api(&self, id: Uuid)
+        -> Result<(), AuthError>
+    {
+        api::update_api_state(&mut *self.pool.conn().await?, id, Some(true), None)
+        .await
+        .inspect(|_| self.invalidate_api(id))
+    }
+
+    pub async fn enable_api(&self, id: Uuid)
+        -> Result<(), AuthError>
+    {
+        api::update_api_state(&mut *self.pool.conn().await?, id, Some(false), None)
+        .await
+        .inspect(|_| self.invalidate_api(id))
+    }
+
+    pub async fn soft_delete_api(&self, id: Uuid)
+        -> Result<(), AuthError>
+    {
+        api::update_api_state(&mut *self.pool.conn().await?, id, None, Some(true))
+        .await
+        .inspect(|_| self.invalidate_api(id))
+    }
+
+    pub async fn restore_api(&self, id: Uuid)
+        -> Result<(), AuthError>
     {
-        api::update_api(&self.pool, id, name, address, category, description, password, access_key)
+        api::update_api_state(&mut *self.pool.conn().await?, id, None, Some(false))
         .await
+        .inspect(|_| self.invalidate_api(id))
     }
 
     pub async fn delete_api(&self, id: Uuid)
-        -> Result<(), Error>
+        -> Result<(), AuthError>
     {
-        api::delete_api(&self.pool, id)
+        api::delete_api(&mut *self.pool.conn().await?, id)
         .await
+        .inspect(|_| self.invalidate_api(id))
+    }
+
+    pub async fn delete_api_cascade(&self, id: Uuid)
+        -> Result<DeleteSummary, AuthError>
+    {
+        cascade::delete_api_cascade(&mut *self.pool.conn().await?, id)
+        .await
+        .inspect(|_| self.invalidate_api(id))
     }
 
     pub async fn read_procedure(&self, id: Uuid)
-        -> Result<ProcedureSchema, Error>
+        -> Result<ProcedureSchema, AuthError>
     {
-        api::select_procedure(&self.pool, Some(id), None, None, None, None).await?
-        .into_iter().next().ok_or(Error::RowNotFound)
+        api::select_procedure(&mut *self.pool.conn().await?, Some(id), None, None, None, None, None, None).await?
+        .into_iter().next().ok_or(AuthError::not_found("procedure"))
     }
 
     pub async fn read_procedure_by_name(&self, api_id: Uuid, name: &str)
-        -> Result<ProcedureSchema, Error>
+        -> Result<ProcedureSchema, AuthError>
     {
-        api::select_procedure(&self.pool, None, None, Some(api_id), Some(name), None).await?
-        .into_iter().next().ok_or(Error::RowNotFound)
+        api::select_procedure(&mut *self.pool.conn().await?, None, None, Some(api_id), Some(name), None, None, None).await?
+        .into_iter().next().ok_or(AuthError::not_found("procedure"))
     }
 
     pub async fn list_procedure_by_ids(&self, ids: &[Uuid])
-        -> Result<Vec<ProcedureSchema>, Error>
+        -> Result<Vec<ProcedureSchema>, AuthError>
     {
-        api::select_procedure(&self.pool, None, Some(ids), None, None, None)
+        api::select_procedure(&mut *self.pool.conn().await?, None, Some(ids), None, None, None, None, Some(&self.options))
         .await
     }
 
     pub async fn list_procedure_by_api(&self, api_id: Uuid)
-        -> Result<Vec<ProcedureSchema>, Error>
+        -> Result<Vec<ProcedureSchema>, AuthError>
     {
-        api::select_procedure(&self.pool, None, None, Some(api_id), None, None)
+        api::select_procedure(&mut *self.pool.conn().await?, None, None, Some(api_id), None, None, None, Some(&self.options))
         .await
     }
 
     pub async fn list_procedure_by_name(&self, name: &str)
-        -> Result<Vec<ProcedureSchema>, Error>
+        -> Result<Vec<ProcedureSchema>, AuthError>
     {
-        api::select_procedure(&self.pool, None, None, None, None, Some(name))
+        let name_like = self.search_pattern(name);
+        api::select_procedure(&mut *self.pool.conn().await?, None, None, None, None, Some(&name_like), None, Some(&self.options))
         .await
     }
 
     pub async fn list_procedure_option(&self, api_id: Option<Uuid>, name: Option<&str>)
-        -> Result<Vec<ProcedureSchema>, Error>
+        -> Result<Vec<ProcedureSchema>, AuthError>
     {
-        api::select_procedure(&self.pool, None, None, api_id, None, name)
+        let name_like = name.map(|v| self.search_pattern(v));
+        api::select_procedure(&mut *self.pool.conn().await?, None, None, api_id, None, name_like.as_deref(), None, Some(&self.options))
+        .await
+    }
+
+    pub async fn count_procedure_option(&self, api_id: Option<Uuid>, name: Option<&str>)
+        -> Result<u64, AuthError>
+    {
+        let name_like = name.map(|v| self.search_pattern(v));
+        api::count_procedure(&mut *self.pool.conn().await?, None, None, api_id, None, name_like.as_deref(), None)
+        .await
+    }
+
+    pub async fn list_procedure_page(&self, api_id: Option<Uuid>, name: Option<&str>, order: PageOrder, size: u32, cursor: Option<&str>)
+        -> Result<Page<ProcedureSchema>, AuthError>
+    {
+        let options = self.page_options(order, size, cursor)?;
+        let name_like = name.map(|v| self.search_pattern(v));
+        let procedures = api::select_procedure(&mut *self.pool.conn().await?, None, None, api_id, None, name_like.as_deref(), None, Some(&options))
+            .await?;
+        Ok(Page::new(procedures, size, |p| (p.name.as_str(), p.id)))
+    }
+
+    pub async fn list_procedure_updated_since(&self, timestamp: DateTime<Utc>)
+        -> Result<Vec<ProcedureSchema>, AuthError>
+    {
+        api::select_procedure(&mut *self.pool.conn().await?, None, None, None, None, None, Some(timestamp), Some(&self.options))
         .await
     }
 
     pub async fn create_procedure(&self, id: Uuid, api_id: Uuid, name: &str, description: &str)
-        -> Result<Uuid, Error>
+        -> Result<Uuid, AuthError>
     {
-        api::insert_procedure(&self.pool, id, api_id, name, description)
+        api::insert_procedure(&mut *self.pool.conn().await?, id, api_id, name, description)
         .await
+        .inspect(|_| self.invalidate_api(api_id))
     }
 
     pub async fn update_procedure(&self, id: Uuid, name: Option<&str>, description: Option<&str>)
-        -> Result<(), Error>
+        -> Result<(), AuthError>
     {
-        api::update_procedure(&self.pool, id, name, description)
-        .await
+        api::update_procedure(&mut *self.pool.conn().await?, id, name, description, None)
+        .await.map(|_| ())
+        .inspect(|_| self.invalidate_cache())
+    }
+
+    pub async fn update_procedure_versioned(&self, id: Uuid, version: i32, name: Option<&str>, description: Option<&str>)
+        -> Result<i32, AuthError>
+    {
+        let rows = api::update_procedure(&mut *self.pool.conn().await?, id, name, description, Some(version)).await?;
+        self.invalidate_cache();
+        if rows == 0 {
+            let procedure = self.read_procedure(id).await?;
+            return Err(AuthError::Conflict { entity: String::from("procedure"), expected: version, actual: procedure.version });
+        }
+        Ok(version + 1)
     }
 
     pub async fn delete_procedure(&self, id: Uuid)
-        -> Result<(), Error>
+        -> Result<(), AuthError>
     {
-        api::delete_procedure(&self.pool, id)
+        api::delete_procedure(&mut *self.pool.conn().await?, id)
         .await
+        .inspect(|_| self.invalidate_cache())
+    }
+
+    pub async fn delete_procedure_cascade(&self, id: Uuid)
+        -> Result<DeleteSummary, AuthError>
+    {
+        cascade::delete_procedure_cascade(&mut *self.pool.conn().await?, id)
+        .await
+        .inspect(|_| self.invalidate_cache())
     }
 
     pub async fn read_role(&self, id: Uuid)
-        -> Result<RoleSchema, Error>
+        -> Result<RoleSchema, AuthError>
     {
-        role::select_role(&self.pool, Some(id), None, None, None, None, None).await?
-        .into_iter().next().ok_or(Error::RowNotFound)
+        if let Some(role) = self.cache.as_ref().and_then(|c| c.get_role(id)) {
+            return Ok(role);
+        }
+        role::select_role(&mut *self.pool.conn().await?, &RoleFilter::default().ids(&[id]), true, None).await?
+        .into_iter().next().ok_or(AuthError::not_found("role"))
+        .inspect(|role| if let Some(cache) = &self.cache { cache.put_role(role) })
     }
 
     pub async fn read_role_by_name(&self, api_id: Uuid, name: &str)
-        -> Result<RoleSchema, Error>
+        -> Result<RoleSchema, AuthError>
+    {
+        role::select_role(&mut *self.pool.conn().await?, &RoleFilter::default().api_id(api_id).names(&[name]), true, None).await?
+        .into_iter().next().ok_or(AuthError::not_found("role"))
+    }
+
+    pub async fn read_role_with_secrets(&self, id: Uuid)
+        -> Result<RoleSchema, AuthError>
+    {
+        let options = AuthOptions::default().with_secrets(true);
+        role::select_role(&mut *self.pool.conn().await?, &RoleFilter::default().ids(&[id]), true, Some(&options)).await?
+        .into_iter().next().ok_or(AuthError::not_found("role"))
+    }
+
+    pub async fn read_role_by_name_with_secrets(&self, api_id: Uuid, name: &str)
+        -> Result<RoleSchema, AuthError>
     {
-        role::select_role(&self.pool, None, None, Some(api_id), None, Some(name), None).await?
-        .into_iter().next().ok_or(Error::RowNotFound)
+        let options = AuthOptions::default().with_secrets(true);
+        role::select_role(&mut *self.pool.conn().await?, &RoleFilter::default().api_id(api_id).names(&[name]), true, Some(&options)).await?
+        .into_iter().next().ok_or(AuthError::not_found("role"))
+    }
+
+    pub async fn list_role(&self, filter: &RoleFilter)
+        -> Result<Vec<RoleSchema>, AuthError>
+    {
+        role::select_role(&mut *self.pool.conn().await?, filter, self.options.with_inactive, Some(&self.options))
+        .await
+    }
+
+    pub async fn count_role(&self, filter: &RoleFilter)
+        -> Result<u64, AuthError>
+    {
+        role::count_role(&mut *self.pool.conn().await?, filter, self.options.with_inactive)
+        .await
     }
 
     pub async fn list_role_by_ids(&self, ids: &[Uuid])
-        -> Result<Vec<RoleSchema>, Error>
+        -> Result<Vec<RoleSchema>, AuthError>
     {
-        role::select_role(&self.pool, None, Some(ids), None, None, None, None)
+        role::select_role(&mut *self.pool.conn().await?, &RoleFilter::default().ids(ids), self.options.with_inactive, Some(&self.options))
         .await
     }
 
     pub async fn list_role_by_api(&self, api_id: Uuid)
-        -> Result<Vec<RoleSchema>, Error>
+        -> Result<Vec<RoleSchema>, AuthError>
     {
-        role::select_role(&self.pool, None, None, Some(api_id), None, None, None)
+        role::select_role(&mut *self.pool.conn().await?, &RoleFilter::default().api_id(api_id), self.options.with_inactive, Some(&self.options))
         .await
     }
 
     pub async fn list_role_by_user(&self, user_id: Uuid)
-        -> Result<Vec<RoleSchema>, Error>
+        -> Result<Vec<RoleSchema>, AuthError>
     {
-        role::select_role(&self.pool, None, None, None, Some(user_id), None, None)
+        role::select_role(&mut *self.pool.conn().await?, &RoleFilter::default().user_id(user_id), self.options.with_inactive, Some(&self.options))
         .await
     }
 
     pub async fn list_role_by_name(&self, name: &str)
-        -> Result<Vec<RoleSchema>, Error>
+        -> Result<Vec<RoleSchema>, AuthError>
     {
-        role::select_role(&self.pool, None, None, None, None, None, Some(name))
+        let name_like = self.search_pattern(name);
+        role::select_role(&mut *self.pool.conn().await?, &RoleFilter::default().name_like(&name_like), self.options.with_inactive, Some(&self.options))
         .await
     }
 
     pub async fn list_role_option(&self, api_id: Option<Uuid>, user_id: Option<Uuid>, name: Option<&str>)
-        -> Result<Vec<RoleSchema>, Error>
+        -> Result<Vec<RoleSchema>, AuthError>
     {
-        role::select_role(&self.pool, None, None, api_id, user_id, None, name)
+        role::select_role(&mut *self.pool.conn().await?, &self.role_option_filter(api_id, user_id, name), self.options.with_inactive, Some(&self.options))
         .await
     }
 
+    pub async fn count_role_option(&self, api_id: Option<Uuid>, user_id: Option<Uuid>, name: Option<&str>)
+        -> Result<u64, AuthError>
+    {
+        role::count_role(&mut *self.pool.conn().await?, &self.role_option_filter(api_id, user_id, name), self.options.with_inactive)
+        .await
+    }
+
+    pub async fn list_role_page(&self, api_id: Option<Uuid>, user_id: Option<Uuid>, name: Option<&str>, order: PageOrder, size: u32, cursor: Option<&str>)
+        -> Result<Page<RoleSchema>, AuthError>
+    {
+        let options = self.page_options(order, size, cursor)?;
+        let roles = role::select_role(&mut *self.pool.conn().await?, &self.role_option_filter(api_id, user_id, name), self.options.with_inactive, Some(&options))
+            .await?;
+        Ok(Page::new(roles, size, |r| (r.name.as_str(), r.id)))
+    }
+
+    pub async fn list_role_updated_since(&self, timestamp: DateTime<Utc>)
+        -> Result<Vec<RoleSchema>, AuthError>
+    {
+        role::select_role(&mut *self.pool.conn().await?, &RoleFilter::default().updated_since(timestamp), true, Some(&self.options))
+        .await
+    }
+
+    pub fn stream_roles<'a>(&'a self, filter: &RoleFilter)
+        -> impl Stream<Item = Result<RoleSchema, AuthError>> + Send + use<'a, E>
+    {
+        let filter = filter.clone();
+        try_stream! {
+            let mut conn = self.pool.conn().await?;
+            for await role in role::stream_role(&mut *conn, &filter, self.options.with_inactive) {
+                yield role?;
+            }
+        }
+    }
+
     pub async fn create_role(&self, id: Uuid, api_id: Uuid, name: &str, multi: bool, ip_lock: bool, access_duration: i32, refresh_duration: i32)
-        -> Result<Uuid, Error>
+        -> Result<Uuid, AuthError>
     {
-        role::insert_role(&self.pool, id, api_id, name, multi, ip_lock, access_duration, refresh_duration)
+        role::insert_role(&mut *self.pool.conn().await?, id, api_id, name, multi, ip_lock, access_duration, refresh_duration)
         .await
     }
 
     pub async fn update_role(&self, id: Uuid, name: Option<&str>, multi: Option<bool>, ip_lock: Option<bool>, access_duration: Option<i32>, refresh_duration: Option<i32>)
-        -> Result<(), Error>
+        -> Result<(), AuthError>
+    {
+        role::update_role(&mut *self.pool.conn().await?, id, name, multi, ip_lock, access_duration, refresh_duration, None)
+        .await.map(|_| ())
+        .inspect(|_| self.invalidate_role(id))
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    pub async fn update_role_versioned(&self, id: Uuid, version: i32, name: Option<&str>, multi: Option<bool>, ip_lock: Option<bool>, access_duration: Option<i32>, refresh_duration: Option<i32>)
+        -> Result<i32, AuthError>
+    {
+        let rows = role::update_role(&mut *self.pool.conn().await?, id, name, multi, ip_lock, access_duration, refresh_duration, Some(version)).await?;
+        self.invalidate_role(id);
+        if rows == 0 {
+            let role = self.read_role(id).await?;
+            return Err(AuthError::Conflict { entity: String::from("role"), expected: version, actual: role.version });
+        }
+        Ok(version + 1)
+    }
+
+    pub async fn disable_role(&self, id: Uuid)
+        -> Result<(), AuthError>
+    {
+        role::update_role_state(&mut *self.pool.conn().await?, id, Some(true), None)
+        .await
+        .inspect(|_| self.invalidate_role(id))
+    }
+
+    pub async fn enable_role(&self, id: Uuid)
+        -> Result<(), AuthError>
+    {
+        role::update_role_state(&mut *self.pool.conn().await?, id, Some(false), None)
+        .await
+        .inspect(|_| self.invalidate_role(id))
+    }
+
+    pub async fn soft_delete_role(&self, id: Uuid)
+        -> Result<(), AuthError>
+    {
+        role::update_role_state(&mut *self.pool.conn().await?, id, None, Some(true))
+        .await
+        .inspect(|_| self.invalidate_role(id))
+    }
+
+    pub async fn restore_role(&self, id: Uuid)
+        -> Result<(), AuthError>
     {
-        role::update_role(&self.pool, id, name, multi, ip_lock, access_duration, refresh_duration)
+        role::update_role_state(&mut *self.pool.conn().await?, id, None, Some(false))
         .await
+        .inspect(|_| self.invalidate_role(id))
     }
 
     pub async fn delete_role(&self, id: Uuid)
-        -> Result<(), Error>
+        -> Result<(), AuthError>
     {
-        role::delete_role(&self.pool, id)
+        role::delete_role(&mut *self.pool.conn().await?, id)
         .await
+        .inspect(|_| self.invalidate_role(id))
+    }
+
+    pub async fn delete_role_cascade(&self, id: Uuid)
+        -> Result<DeleteSummary, AuthError>
+    {
+        cascade::delete_role_cascade(&mut *self.pool.conn().await?, id)
+        .await
+        .inspect(|_| self.invalidate_role(id))
+    }
+
+    pub async fn clone_role(&self, source_role_id: Uuid, target_api_id: Uuid, new_name: &str)
+        -> Result<(Uuid, Vec<String>), AuthError>
+    {
+        let id = Uuid::new_v4();
+        let unmatched = role::clone_role(&mut *self.pool.conn().await?, source_role_id, id, target_api_id, new_name).await?;
+        Ok((id, unmatched))
     }
 
     pub async fn add_role_access(&self, id: Uuid, procedure_id: Uuid)
-        -> Result<(), Error>
+        -> Result<(), AuthError>
     {
-        role::add_role_access(&self.pool, id, procedure_id)
+        role::add_role_access(&mut *self.pool.conn().await?, id, procedure_id, false)
         .await
+        .inspect(|_| self.invalidate_role(id))
     }
 
     pub async fn remove_role_access(&self, id: Uuid, procedure_id: Uuid)
-        -> Result<(), Error>
+        -> Result<(), AuthError>
+    {
+        role::remove_role_access(&mut *self.pool.conn().await?, id, procedure_id, false)
+        .await
+        .inspect(|_| self.invalidate_role(id))
+    }
+
+    pub async fn add_role_deny(&self, id: Uuid, procedure_id: Uuid)
+        -> Result<(), AuthError>
+    {
+        role::add_role_access(&mut *self.pool.conn().await?, id, procedure_id, true)
+        .await
+        .inspect(|_| self.invalidate_role(id))
+    }
+
+    pub async fn remove_role_deny(&self, id: Uuid, procedure_id: Uuid)
+        -> Result<(), AuthError>
+    {
+        role::remove_role_access(&mut *self.pool.conn().await?, id, procedure_id, true)
+        .await
+        .inspect(|_| self.invalidate_role(id))
+    }
+
+    pub async fn check_access(&self, user_id: Uuid, procedure_id: Uuid)
+        -> Result<bool, AuthError>
+    {
+        if let Some(access) = self.cache.as_ref().and_then(|c| c.get_access(user_id, procedure_id)) {
+            return Ok(access);
+        }
+        access::select_access(&mut *self.pool.conn().await?, user_id, procedure_id, &[])
+        .await
+        .inspect(|&access| if let Some(cache) = &self.cache { cache.put_access(user_id, procedure_id, access) })
+    }
+
+    pub async fn check_access_with_params(&self, user_id: Uuid, procedure_id: Uuid, params: &[(&str, DataValue)])
+        -> Result<bool, AuthError>
+    {
+        access::select_access(&mut *self.pool.conn().await?, user_id, procedure_id, params)
+        .await
+    }
+
+    pub async fn permission_matrix(&self, api_id: Uuid)
+        -> Result<PermissionMatrix, AuthError>
+    {
+        let permissions = access::select_permission(&mut *self.pool.conn().await?, api_id).await?;
+        Ok(PermissionMatrix { api_id, permissions })
+    }
+
+    pub async fn read_access_condition(&self, id: i32)
+        -> Result<AccessConditionSchema, AuthError>
+    {
+        access::select_access_condition(&mut *self.pool.conn().await?, Some(id), None, None).await?
+        .into_iter().next().ok_or(AuthError::not_found("access condition"))
+    }
+
+    pub async fn list_access_condition_by_role(&self, role_id: Uuid)
+        -> Result<Vec<AccessConditionSchema>, AuthError>
     {
-        role::remove_role_access(&self.pool, id, procedure_id)
+        access::select_access_condition(&mut *self.pool.conn().await?, None, Some(&[role_id]), None)
         .await
     }
 
+    pub async fn list_access_condition_by_access(&self, role_id: Uuid, procedure_id: Uuid)
+        -> Result<Vec<AccessConditionSchema>, AuthError>
+    {
+        access::select_access_condition(&mut *self.pool.conn().await?, None, Some(&[role_id]), Some(procedure_id))
+        .await
+    }
+
+    pub async fn create_access_condition(&self, role_id: Uuid, procedure_id: Uuid, profile: &str, operator: ConditionOperator, values: &[DataValue], param: Option<&str>)
+        -> Result<i32, AuthError>
+    {
+        access::insert_access_condition(&mut *self.pool.conn().await?, role_id, procedure_id, profile, operator, values, param)
+        .await
+        .inspect(|_| self.invalidate_access())
+    }
+
+    pub async fn update_access_condition(&self, id: i32, profile: Option<&str>, operator: Option<ConditionOperator>, values: Option<&[DataValue]>, param: Option<Option<&str>>)
+        -> Result<(), AuthError>
+    {
+        access::update_access_condition(&mut *self.pool.conn().await?, id, profile, operator, values, param)
+        .await
+        .inspect(|_| self.invalidate_access())
+    }
+
+    pub async fn delete_access_condition(&self, id: i32)
+        -> Result<(), AuthError>
+    {
+        access::delete_access_condition(&mut *self.pool.conn().await?, id)
+        .await
+        .inspect(|_| self.invalidate_access())
+    }
+
     pub async fn read_role_profile(&self, id: i32)
-        -> Result<RoleProfileSchema, Error>
+        -> Result<RoleProfileSchema, AuthError>
     {
-        profile::select_role_profile(&self.pool, Some(id), None).await?
-        .into_iter().next().ok_or(Error::RowNotFound)
+        profile::select_role_profile(&mut *self.pool.conn().await?, Some(id), None, None, None).await?
+        .into_iter().next().ok_or(AuthError::not_found("role profile"))
     }
 
     pub async fn list_role_profile_by_role(&self, role_id: Uuid)
-        -> Result<Vec<RoleProfileSchema>, Error>
+        -> Result<Vec<RoleProfileSchema>, AuthError>
+    {
+        profile::select_role_profile(&mut *self.pool.conn().await?, None, Some(role_id), None, None)
+        .await
+    }
+
+    pub async fn list_role_profile_updated_since(&self, timestamp: DateTime<Utc>)
+        -> Result<Vec<RoleProfileSchema>, AuthError>
     {
-        profile::select_role_profile(&self.pool, None, Some(role_id))
+        profile::select_role_profile(&mut *self.pool.conn().await?, None, None, None, Some(timestamp))
         .await
     }
 
+    pub async fn list_role_profile_by_roles(&self, role_ids: &[Uuid])
+        -> Result<HashMap<Uuid, Vec<RoleProfileSchema>>, AuthError>
+    {
+        let mut profile_map: HashMap<Uuid, Vec<RoleProfileSchema>> = role_ids.iter().map(|&id| (id, Vec::new())).collect();
+        for profile in profile::select_role_profile(&mut *self.pool.conn().await?, None, None, Some(role_ids), None).await? {
+            profile_map.entry(profile.role_id).or_default().push(profile);
+        }
+        Ok(profile_map)
+    }
+
     pub async fn create_role_profile(&self, role_id: Uuid, name: &str, value_type: DataType, mode: ProfileMode)
-        -> Result<i32, Error>
+        -> Result<i32, AuthError>
     {
-        profile::insert_role_profile(&self.pool, role_id, name, value_type, mode)
+        profile::insert_role_profile(&mut *self.pool.conn().await?, role_id, name, value_type, mode)
         .await
+        .inspect(|_| self.invalidate_access())
     }
 
     pub async fn update_role_profile(&self, id: i32, name: Option<&str>, value_type: Option<DataType>, mode: Option<ProfileMode>)
-        -> Result<(), Error>
+        -> Result<(), AuthError>
     {
-        profile::update_role_profile(&self.pool, id, name, value_type, mode)
+        profile::update_role_profile(&mut *self.pool.conn().await?, id, name, value_type, mode)
         .await
+        .inspect(|_| self.invalidate_access())
     }
 
     pub async fn delete_role_profile(&self, id: i32)
-        -> Result<(), Error>
+        -> Result<(), AuthError>
     {
-        profile::delete_role_profile(&self.pool, id)
+        profile::delete_role_profile(&mut *self.pool.conn().await?, id)
         .await
+        .inspect(|_| self.invalidate_access())
     }
 
     pub async fn read_user(&self, id: Uuid)
-        -> Result<UserSchema, Error>
+        -> Result<UserSchema, AuthError>
     {
-        user::select_user(&self.pool, Some(id), None, None, None, None, None).await?
-        .into_iter().next().ok_or(Error::RowNotFound)
+        if let Some(user) = self.cache.as_ref().and_then(|c| c.get_user(id)) {
+            return Ok(user);
+        }
+        user::select_user(&mut *self.pool.conn().await?, &UserFilter::default().ids(&[id]), true, None).await?
+        .into_iter().next().ok_or(AuthError::not_found("user"))
+        .inspect(|user| if let Some(cache) = &self.cache { cache.put_user(user) })
     }
 
     pub async fn read_user_by_name(&self, name: &str)
-        -> Result<UserSchema, Error>
+        -> Result<UserSchema, AuthError>
+    {
+        user::select_user(&mut *self.pool.conn().await?, &UserFilter::default().names(&[name]), true, None).await?
+        .into_iter().next().ok_or(AuthError::not_found("user"))
+    }
+
+    pub async fn read_user_by_email(&self, email: &str)
+        -> Result<UserSchema, AuthError>
+    {
+        // contact uniqueness is optional so more than one match is ambiguous
+        let mut users = user::select_user(&mut *self.pool.conn().await?, &UserFilter::default().email(email), true, None).await?;
+        if users.len() > 1 {
+            return Err(AuthError::Duplicate { field: String::from("email") });
+        }
+        users.pop().ok_or(AuthError::not_found("user"))
+    }
+
+    pub async fn read_user_by_phone(&self, phone: &str)
+        -> Result<UserSchema, AuthError>
+    {
+        // contact uniqueness is optional so more than one match is ambiguous
+        let mut users = user::select_user(&mut *self.pool.conn().await?, &UserFilter::default().phone(phone), true, None).await?;
+        if users.len() > 1 {
+            return Err(AuthError::Duplicate { field: String::from("phone") });
+        }
+        users.pop().ok_or(AuthError::not_found("user"))
+    }
+
+    pub async fn read_user_by_identifier(&self, identifier: &str)
+        -> Result<UserSchema, AuthError>
+    {
+        // try to match user name first then email and phone
+        match self.read_user_by_name(identifier).await {
+            Err(AuthError::NotFound { .. }) => {},
+            result => return result
+        }
+        if identifier.contains('@') {
+            self.read_user_by_email(identifier).await
+        } else {
+            self.read_user_by_phone(identifier).await
+        }
+    }
+
+    pub async fn read_user_with_secrets(&self, id: Uuid)
+        -> Result<UserSchema, AuthError>
+    {
+        let options = AuthOptions::default().with_secrets(true);
+        user::select_user(&mut *self.pool.conn().await?, &UserFilter::default().ids(&[id]), true, Some(&options)).await?
+        .into_iter().next().ok_or(AuthError::not_found("user"))
+    }
+
+    pub async fn read_user_by_name_with_secrets(&self, name: &str)
+        -> Result<UserSchema, AuthError>
+    {
+        let options = AuthOptions::default().with_secrets(true);
+        user::select_user(&mut *self.pool.conn().await?, &UserFilter::default().names(&[name]), true, Some(&options)).await?
+        .into_iter().next().ok_or(AuthError::not_found("user"))
+    }
+
+    pub async fn read_user_with_profiles(&self, id: Uuid)
+        -> Result<UserSchema, AuthError>
     {
-        user::select_user(&self.pool, None, None, None, None, Some(name), None).await?
-        .into_iter().next().ok_or(Error::RowNotFound)
+        let options = AuthOptions::default().with_profiles(true);
+        user::select_user(&mut *self.pool.conn().await?, &UserFilter::default().ids(&[id]), true, Some(&options)).await?
+        .into_iter().next().ok_or(AuthError::not_found("user"))
+    }
+
+    pub async fn read_user_by_identifier_with_secrets(&self, identifier: &str)
+        -> Result<UserSchema, AuthError>
+    {
+        let user = self.read_user_by_identifier(identifier).await?;
+        self.read_user_with_secrets(user.id).await
+    }
+
+    pub async fn list_user(&self, filter: &UserFilter)
+        -> Result<Vec<UserSchema>, AuthError>
+    {
+        user::select_user(&mut *self.pool.conn().await?, filter, self.options.with_inactive, Some(&self.options))
+        .await
+    }
+
+    pub async fn count_user(&self, filter: &UserFilter)
+        -> Result<u64, AuthError>
+    {
+        user::count_user(&mut *self.pool.conn().await?, filter, self.options.with_inactive)
+        .await
+    }
+
+    pub async fn list_duplicate_contact(&self)
+        -> Result<Vec<DuplicateContactSchema>, AuthError>
+    {
+        user::select_duplicate_contact(&mut *self.pool.conn().await?)
+        .await
+    }
+
+    // opt-in unique email and phone, resolve users listed by list_duplicate_contact before enabling
+    pub async fn enable_unique_contact(&self)
+        -> Result<(), AuthError>
+    {
+        user::create_unique_contact(&mut *self.pool.conn().await?)
+        .await
+    }
+
+    pub async fn disable_unique_contact(&self)
+        -> Result<(), AuthError>
+    {
+        user::drop_unique_contact(&mut *self.pool.conn().await?)
+        .await
     }
 
     pub async fn list_user_by_ids(&self, ids: &[Uuid])
-        -> Result<Vec<UserSchema>, Error>
+        -> Result<Vec<UserSchema>, AuthError>
     {
-        user::select_user(&self.pool, None, Some(ids), None, None, None, None)
+        user::select_user(&mut *self.pool.conn().await?, &UserFilter::default().ids(ids), self.options.with_inactive, Some(&self.options))
         .await
     }
 
     pub async fn list_user_by_api(&self, api_id: Uuid)
-        -> Result<Vec<UserSchema>, Error>
+        -> Result<Vec<UserSchema>, AuthError>
     {
-        user::select_user(&self.pool, None, None, Some(api_id), None, None, None)
+        user::select_user(&mut *self.pool.conn().await?, &UserFilter::default().api_id(api_id), self.options.with_inactive, Some(&self.options))
         .await
     }
 
     pub async fn list_user_by_role(&self, role_id: Uuid)
-        -> Result<Vec<UserSchema>, Error>
+        -> Result<Vec<UserSchema>, AuthError>
     {
-        user::select_user(&self.pool, None, None, None, Some(role_id), None, None)
+        user::select_user(&mut *self.pool.conn().await?, &UserFilter::default().has_role(role_id), self.options.with_inactive, Some(&self.options))
         .await
     }
 
     pub async fn list_user_by_name(&self, name: &str)
-        -> Result<Vec<UserSchema>, Error>
+        -> Result<Vec<UserSchema>, AuthError>
     {
-        user::select_user(&self.pool, None, None, None, None, None, Some(name))
+        let name_like = self.search_pattern(name);
+        user::select_user(&mut *self.pool.conn().await?, &UserFilter::default().name_like(&name_like), self.options.with_inactive, Some(&self.options))
         .await
     }
 
     pub async fn list_user_option(&self, api_id: Option<Uuid>, role_id: Option<Uuid>, name: Option<&str>)
-        -> Result<Vec<UserSchema>, Error>
+        -> Result<Vec<UserSchema>, AuthError>
     {
-        user::select_user(&self.pool, None, None, api_id, role_id, None, name)
+        user::select_user(&mut *self.pool.conn().await?, &self.user_option_filter(api_id, role_id, name), self.options.with_inactive, Some(&self.options))
         .await
     }
 
+    pub async fn count_user_option(&self, api_id: Option<Uuid>, role_id: Option<Uuid>, name: Option<&str>)
+        -> Result<u64, AuthError>
+    {
+        user::count_user(&mut *self.pool.conn().await?, &self.user_option_filter(api_id, role_id, name), self.options.with_inactive)
+        .await
+    }
+
+    pub async fn list_user_page(&self, api_id: Option<Uuid>, role_id: Option<Uuid>, name: Option<&str>, order: PageOrder, size: u32, cursor: Option<&str>)
+        -> Result<Page<UserSchema>, AuthError>
+    {
+        let options = self.page_options(order, size, cursor)?;
+        let users = user::select_user(&mut *self.pool.conn().await?, &self.user_option_filter(api_id, role_id, name), self.options.with_inactive, Some(&options))
+            .await?;
+        Ok(Page::new(users, size, |u| (u.name.as_str(), u.id)))
+    }
+
+    pub async fn list_user_updated_since(&self, timestamp: DateTime<Utc>)
+        -> Result<Vec<UserSchema>, AuthError>
+    {
+        user::select_user(&mut *self.pool.conn().await?, &UserFilter::default().updated_since(timestamp), true, Some(&self.options))
+        .await
+    }
+
+    pub fn stream_users<'a>(&'a self, filter: &UserFilter)
+        -> impl Stream<Item = Result<UserSchema, AuthError>> + Send + use<'a, E>
+    {
+        let filter = filter.clone();
+        try_stream! {
+            let mut conn = self.pool.conn().await?;
+            for await user in user::stream_user(&mut *conn, &filter, self.options.with_inactive) {
+                yield user?;
+            }
+        }
+    }
+
     pub async fn create_user(&self, id: Uuid, name: &str, email: &str, phone: &str, password: &str)
-        -> Result<Uuid, Error>
+        -> Result<Uuid, AuthError>
     {
-        user::insert_user(&self.pool, id, name, email, phone, password)
+        user::insert_user(&mut *self.pool.conn().await?, id, name, email, phone, password)
         .await
     }
 
     pub async fn update_user(&self, id: Uuid, name: Option<&str>, email: Option<&str>, phone: Option<&str>, password: Option<&str>)
-        -> Result<(), Error>
+        -> Result<(), AuthError>
+    {
+        user::update_user(&mut *self.pool.conn().await?, id, name, email, phone, password, None)
+        .await.map(|_| ())
+        .inspect(|_| self.invalidate_user(id))
+    }
+
+    pub async fn update_user_versioned(&self, id: Uuid, version: i32, name: Option<&str>, email: Option<&str>, phone: Option<&str>, password: Option<&str>)
+        -> Result<i32, AuthError>
+    {
+        let rows = user::update_user(&mut *self.pool.conn().await?, id, name, email, phone, password, Some(version)).await?;
+        self.invalidate_user(id);
+        if rows == 0 {
+            let user = self.read_user(id).await?;
+            return Err(AuthError::Conflict { entity: String::from("user"), expected: version, actual: user.version });
+        }
+        Ok(version + 1)
+    }
+
+    pub async fn disable_user(&self, id: Uuid)
+        -> Result<(), AuthError>
+    {
+        user::update_user_state(&mut *self.pool.conn().await?, id, Some(true), None)
+        .await
+        .inspect(|_| self.invalidate_user(id))
+    }
+
+    pub async fn enable_user(&self, id: Uuid)
+        -> Result<(), AuthError>
+    {
+        user::update_user_state(&mut *self.pool.conn().await?, id, Some(false), None)
+        .await
+        .inspect(|_| self.invalidate_user(id))
+    }
+
+    pub async fn soft_delete_user(&self, id: Uuid)
+        -> Result<(), AuthError>
     {
-        user::update_user(&self.pool, id, name, email, phone, password)
+        user::update_user_state(&mut *self.pool.conn().await?, id, None, Some(true))
         .await
+        .inspect(|_| self.invalidate_user(id))
+    }
+
+    pub async fn restore_user(&self, id: Uuid)
+        -> Result<(), AuthError>
+    {
+        user::update_user_state(&mut *self.pool.conn().await?, id, None, Some(false))
+        .await
+        .inspect(|_| self.invalidate_user(id))
     }
 
     pub async fn delete_user(&self, id: Uuid)
-        -> Result<(), Error>
+        -> Result<u64, AuthError>
     {
-        user::delete_user(&self.pool, id)
+        user::delete_user(&mut *self.pool.conn().await?, id)
         .await
+        .inspect(|_| self.invalidate_user(id))
+    }
+
+    pub async fn delete_user_cascade(&self, id: Uuid)
+        -> Result<DeleteSummary, AuthError>
+    {
+        cascade::delete_user_cascade(&mut *self.pool.conn().await?, id)
+        .await
+        .inspect(|_| self.invalidate_user(id))
     }
 
     pub async fn add_user_role(&self, id: Uuid, role_id: Uuid)
-        -> Result<(), Error>
+        -> Result<(), AuthError>
     {
-        user::add_user_role(&self.pool, id, role_id)
+        user::add_user_role(&mut *self.pool.conn().await?, id, role_id)
         .await
+        .inspect(|_| self.invalidate_user(id))
     }
 
     pub async fn remove_user_role(&self, id: Uuid, role_id: Uuid)
-        -> Result<(), Error>
+        -> Result<(), AuthError>
     {
-        user::remove_user_role(&self.pool, id, role_id)
+        user::remove_user_role(&mut *self.pool.conn().await?, id, role_id)
         .await
+        .inspect(|_| self.invalidate_user(id))
     }
 
     pub async fn read_user_profile(&self, id: i32)
-        -> Result<UserProfileSchema, Error>
+        -> Result<UserProfileSchema, AuthError>
     {
-        profile::select_user_profile(&self.pool, Some(id), None).await?
-        .into_iter().next().ok_or(Error::RowNotFound)
+        profile::select_user_profile(&mut *self.pool.conn().await?, Some(id), None, None, None).await?
+        .into_iter().next().ok_or(AuthError::not_found("user profile"))
     }
 
     pub async fn list_user_profile_by_user(&self, user_id: Uuid)
-        -> Result<Vec<UserProfileSchema>, Error>
+        -> Result<Vec<UserProfileSchema>, AuthError>
+    {
+        profile::select_user_profile(&mut *self.pool.conn().await?, None, Some(user_id), None, None)
+        .await
+    }
+
+    pub async fn list_user_profile_updated_since(&self, timestamp: DateTime<Utc>)
+        -> Result<Vec<UserProfileSchema>, AuthError>
     {
-        profile::select_user_profile(&self.pool, None, Some(user_id))
+        profile::select_user_profile(&mut *self.pool.conn().await?, None, None, None, Some(timestamp))
         .await
     }
 
+    pub async fn list_user_profile_by_users(&self, user_ids: &[Uuid])
+        -> Result<HashMap<Uuid, Vec<UserProfileSchema>>, AuthError>
+    {
+        let mut profile_map: HashMap<Uuid, Vec<UserProfileSchema>> = user_ids.iter().map(|&id| (id, Vec::new())).collect();
+        for profile in profile::select_user_profile(&mut *self.pool.conn().await?, None, None, Some(user_ids), None).await? {
+            profile_map.entry(profile.user_id).or_default().push(profile);
+        }
+        Ok(profile_map)
+    }
+
     pub async fn create_user_profile(&self, user_id: Uuid, name: &str, value: DataValue)
-        -> Result<i32, Error>
+        -> Result<i32, AuthError>
     {
-        profile::insert_user_profile(&self.pool, user_id, name, value)
+        profile::insert_user_profile(&mut *self.pool.conn().await?, user_id, name, value)
         .await
+        .inspect(|_| self.invalidate_user(user_id))
     }
 
     pub async fn update_user_profile(&self, id: i32, name: Option<&str>, value: Option<DataValue>)
-        -> Result<(), Error>
+        -> Result<(), AuthError>
     {
-        profile::update_user_profile(&self.pool, id, name, value)
+        profile::update_user_profile(&mut *self.pool.conn().await?, id, name, value)
         .await
+        .inspect(|_| self.invalidate_access())
     }
 
     pub async fn delete_user_profile(&self, id: i32)
-        -> Result<(), Error>
+        -> Result<(), AuthError>
     {
-        profile::delete_user_profile(&self.pool, id)
+        profile::delete_user_profile(&mut *self.pool.conn().await?, id)
         .await
+        .inspect(|_| self.invalidate_access())
     }
 
     pub async fn swap_user_profile(&self, user_id: Uuid, name: &str, order_1: i16, order_2: i16)
-        -> Result<(), Error>
+        -> Result<(), AuthError>
+    {
+        profile::swap_user_profile(&mut *self.pool.conn().await?, user_id, name, order_1, order_2)
+        .await
+        .inspect(|_| self.invalidate_user(user_id))
+    }
+
+    pub async fn read_group(&self, id: Uuid)
+        -> Result<GroupSchema, AuthError>
+    {
+        group::select_group(&mut *self.pool.conn().await?, Some(id), None, None, None, None, None).await?
+        .into_iter().next().ok_or(AuthError::not_found("group"))
+    }
+
+    pub async fn read_group_by_name(&self, name: &str)
+        -> Result<GroupSchema, AuthError>
+    {
+        group::select_group(&mut *self.pool.conn().await?, None, None, None, Some(name), None, None).await?
+        .into_iter().next().ok_or(AuthError::not_found("group"))
+    }
+
+    pub async fn list_group_by_ids(&self, ids: &[Uuid])
+        -> Result<Vec<GroupSchema>, AuthError>
     {
-        profile::swap_user_profile(&self.pool, user_id, name, order_1, order_2)
+        group::select_group(&mut *self.pool.conn().await?, None, Some(ids), None, None, None, Some(&self.options))
         .await
     }
 
+    pub async fn list_group_by_user(&self, user_id: Uuid)
+        -> Result<Vec<GroupSchema>, AuthError>
+    {
+        group::select_group(&mut *self.pool.conn().await?, None, None, Some(user_id), None, None, Some(&self.options))
+        .await
+    }
+
+    pub async fn list_group_by_name(&self, name: &str)
+        -> Result<Vec<GroupSchema>, AuthError>
+    {
+        let name_like = self.search_pattern(name);
+        group::select_group(&mut *self.pool.conn().await?, None, None, None, None, Some(&name_like), Some(&self.options))
+        .await
+    }
+
+    pub async fn create_group(&self, id: Uuid, name: &str, description: &str)
+        -> Result<Uuid, AuthError>
+    {
+        group::insert_group(&mut *self.pool.conn().await?, id, name, description)
+        .await
+    }
+
+    pub async fn update_group(&self, id: Uuid, name: Option<&str>, description: Option<&str>)
+        -> Result<(), AuthError>
+    {
+        group::update_group(&mut *self.pool.conn().await?, id, name, description)
+        .await
+    }
+
+    pub async fn delete_group(&self, id: Uuid)
+        -> Result<(), AuthError>
+    {
+        group::delete_group(&mut *self.pool.conn().await?, id)
+        .await
+        .inspect(|_| self.invalidate_cache())
+    }
+
+    pub async fn delete_group_cascade(&self, id: Uuid)
+        -> Result<DeleteSummary, AuthError>
+    {
+        cascade::delete_group_cascade(&mut *self.pool.conn().await?, id)
+        .await
+        .inspect(|_| self.invalidate_cache())
+    }
+
+    pub async fn add_group_user(&self, id: Uuid, user_id: Uuid)
+        -> Result<(), AuthError>
+    {
+        group::add_group_user(&mut *self.pool.conn().await?, id, user_id)
+        .await
+        .inspect(|_| self.invalidate_user(user_id))
+    }
+
+    pub async fn remove_group_user(&self, id: Uuid, user_id: Uuid)
+        -> Result<(), AuthError>
+    {
+        group::remove_group_user(&mut *self.pool.conn().await?, id, user_id)
+        .await
+        .inspect(|_| self.invalidate_user(user_id))
+    }
+
+    pub async fn add_group_role(&self, id: Uuid, role_id: Uuid)
+        -> Result<(), AuthError>
+    {
+        group::add_group_role(&mut *self.pool.conn().await?, id, role_id)
+        .await
+        .inspect(|_| self.invalidate_cache())
+    }
+
+    pub async fn remove_group_role(&self, id: Uuid, role_id: Uuid)
+        -> Result<(), AuthError>
+    {
+        group::remove_group_role(&mut *self.pool.conn().await?, id, role_id)
+        .await
+        .inspect(|_| self.invalidate_cache())
+    }
+
     pub async fn read_access_token(&self, access_id: i32)
-        -> Result<TokenSchema, Error>
+        -> Result<TokenSchema, AuthError>
     {
-        match token::select_token(&self.pool, TokenSelector::Access(access_id)).await?.into_iter().next() {
+        match token::select_token(&mut *self.pool.conn().await?, TokenSelector::Access(access_id)).await?.into_iter().next() {
             Some(value) => Ok(value),
-            None => Err(Error::RowNotFound)
+            None => Err(AuthError::not_found("token"))
         }
     }
 
     pub async fn list_auth_token(&self, auth_token: &str)
-        -> Result<Vec<TokenSchema>, Error>
+        -> Result<Vec<TokenSchema>, AuthError>
     {
-        token::select_token(&self.pool, TokenSelector::Auth(String::from(auth_token)))
+        token::select_token(&mut *self.pool.conn().await?, TokenSelector::Auth(String::from(auth_token)))
         .await
     }
 
     pub async fn list_token_by_user(&self, user_id: Uuid)
-        -> Result<Vec<TokenSchema>, Error>
+        -> Result<Vec<TokenSchema>, AuthError>
     {
-        token::select_token(&self.pool, TokenSelector::User(user_id))
+        token::select_token(&mut *self.pool.conn().await?, TokenSelector::User(user_id))
         .await
     }
 
+    pub async fn count_token_by_user(&self, user_id: Uuid)
+        -> Result<u64, AuthError>
+    {
+        token::count_token(&mut *self.pool.conn().await?, TokenSelector::User(user_id))
+        .await
+    }
+
+    pub fn stream_tokens(&self)
+        -> impl Stream<Item = Result<TokenSchema, AuthError>> + Send + '_
+    {
+        try_stream! {
+            let mut conn = self.pool.conn().await?;
+            for await token in token::stream_token(&mut *conn, None) {
+                yield token?;
+            }
+        }
+    }
+
+    pub fn stream_tokens_by_user(&self, user_id: Uuid)
+        -> impl Stream<Item = Result<TokenSchema, AuthError>> + Send + '_
+    {
+        try_stream! {
+            let mut conn = self.pool.conn().await?;
+            for await token in token::stream_token(&mut *conn, Some(TokenSelector::User(user_id))) {
+                yield token?;
+            }
+        }
+    }
+
     pub async fn create_access_token(&self, user_id: Uuid, auth_token: &str, expire: DateTime<Utc>, ip: &[u8])
-        -> Result<(i32, String, String), Error>
+        -> Result<(i32, String, String), AuthError>
     {
-        token::insert_token(&self.pool, user_id, Some(auth_token), expire, ip, 1)
-        .await?.into_iter().next().ok_or(Error::RowNotFound)
+        token::insert_token(&mut *self.pool.conn().await?, user_id, Some(auth_token), expire, ip, 1)
+        .await?.into_iter().next().ok_or(AuthError::not_found("token"))
     }
 
     pub async fn create_auth_token(&self, user_id: Uuid, expire: DateTime<Utc>, ip: &[u8], number: u32)
-        -> Result<Vec<(i32, String, String)>, Error>
+        -> Result<Vec<(i32, String, String)>, AuthError>
     {
-        token::insert_token(&self.pool, user_id, None, expire, ip, number)
+        token::insert_token(&mut *self.pool.conn().await?, user_id, None, expire, ip, number)
         .await
     }
 
     pub async fn update_access_token(&self, access_id: i32, expire: Option<DateTime<Utc>>, ip: Option<&[u8]>)
-        -> Result<(String, String), Error>
+        -> Result<(String, String), AuthError>
     {
-        token::update_token(&self.pool, Some(access_id), None, expire, ip)
+        token::update_token(&mut *self.pool.conn().await?, Some(access_id), None, expire, ip)
         .await
     }
 
     pub async fn update_auth_token(&self, auth_token: &str, expire: Option<DateTime<Utc>>, ip: Option<&[u8]>)
-        -> Result<(String, String), Error>
+        -> Result<(String, String), AuthError>
     {
-        token::update_token(&self.pool, None, Some(auth_token), expire, ip)
+        token::update_token(&mut *self.pool.conn().await?, None, Some(auth_token), expire, ip)
         .await
     }
 
     pub async fn delete_access_token(&self, access_id: i32)
-        -> Result<(), Error>
+        -> Result<(), AuthError>
     {
-        token::delete_token(&self.pool, TokenSelector::Access(access_id))
+        token::delete_token(&mut *self.pool.conn().await?, TokenSelector::Access(access_id))
         .await
     }
 
     pub async fn delete_auth_token(&self, auth_token: &str)
-        -> Result<(), Error>
+        -> Result<(), AuthError>
     {
-        token::delete_token(&self.pool, TokenSelector::Auth(auth_token.to_owned()))
+        token::delete_token(&mut *self.pool.conn().await?, TokenSelector::Auth(auth_token.to_owned()))
         .await
     }
 
     pub async fn delete_token_by_user(&self, user_id: Uuid)
-        -> Result<(), Error>
+        -> Result<(), AuthError>
     {
-        token::delete_token(&self.pool, TokenSelector::User(user_id))
+        token::delete_token(&mut *self.pool.conn().await?, TokenSelector::User(user_id))
         .await
     }
 
-}
+}
\ No newline at end of file
@@ -0,0 +1,389 @@
+use sqlx::{Acquire, Row};
+use sqlx::postgres::{Postgres, PgRow};
+use sea_query::{PostgresQueryBuilder, Query, Expr, Order, Value, Alias, JoinType, SelectStatement};
+use sea_query_binder::SqlxBinder;
+use uuid::Uuid;
+
+use crate::schema::auth_role::{Role, RoleAccess, RoleAccessCondition, AccessConditionSchema, ConditionOperator};
+use crate::schema::auth_user::{User, UserRole};
+use crate::schema::api::ApiProcedure;
+use crate::schema::permission::PermissionSchema;
+use crate::operation::group::user_role_union;
+use crate::operation::user::active_user;
+use crate::operation::api::active_api;
+use crate::schema::profile::ProfileUser;
+use crate::error::AuthError;
+use rmcs_resource_db::schema::value::{DataValue, DataType};
+
+pub async fn select_access<'c, A>(conn: A, 
+    user_id: Uuid,
+    procedure_id: Uuid,
+    params: &[(&str, DataValue)]
+) -> Result<bool, AuthError> 
+where A: Acquire<'c, Database = Postgres>
+{
+    let mut conn = conn.acquire().await?;
+    let (sql, values) = Query::select()
+        .columns([
+            (RoleAccess::Table, RoleAccess::RoleId),
+            (RoleAccess::Table, RoleAccess::Deny)
+        ])
+        .from_subquery(user_role_union(), Alias::new("user_role"))
+        .inner_join(Role::Table, 
+            Expr::col((UserRole::Table, UserRole::RoleId))
+            .equals((Role::Table, Role::RoleId))
+        )
+        .inner_join(RoleAccess::Table, 
+            Expr::col((Role::Table, Role::RoleId))
+            .equals((RoleAccess::Table, RoleAccess::RoleId))
+        )
+        .inner_join(ApiProcedure::Table, 
+            Expr::col((RoleAccess::Table, RoleAccess::ProcedureId))
+            .equals((ApiProcedure::Table, ApiProcedure::ProcedureId))
+            .and(Expr::col((ApiProcedure::Table, ApiProcedure::ApiId)).equals((Role::Table, Role::ApiId)))
+        )
+        .and_where(Expr::col((UserRole::Table, UserRole::UserId)).eq(user_id))
+        .and_where(Expr::col((RoleAccess::Table, RoleAccess::ProcedureId)).eq(procedure_id))
+        .and_where(Expr::col((UserRole::Table, UserRole::UserId)).in_subquery(active_user()))
+        .and_where(Expr::col((Role::Table, Role::Disabled)).eq(false))
+        .and_where(Expr::col((Role::Table, Role::DeletedAt)).is_null())
+        .and_where(Expr::col((Role::Table, Role::ApiId)).in_subquery(active_api()))
+        .build_sqlx(PostgresQueryBuilder);
+
+    let grants: Vec<(Uuid, bool)> = sqlx::query_with(&sql, values)
+        .map(|row: PgRow| (row.get(0), row.get(1)))
+        .fetch_all(&mut *conn)
+        .await?;
+    if grants.is_empty() {
+        return Ok(false);
+    }
+
+    // read conditions of the granted access and user profiles only if any condition found
+    let role_ids: Vec<Uuid> = grants.iter().map(|(role_id, _)| *role_id).collect();
+    let conditions = select_access_condition(&mut *conn, None, Some(&role_ids), Some(procedure_id)).await?;
+    let profiles: Vec<(String, DataValue)> = if conditions.is_empty() {
+        Vec::new()
+    } else {
+        let (sql, values) = Query::select()
+            .columns([
+                ProfileUser::Name,
+                ProfileUser::Value,
+                ProfileUser::Type
+            ])
+            .from(ProfileUser::Table)
+            .and_where(Expr::col(ProfileUser::UserId).eq(user_id))
+            .build_sqlx(PostgresQueryBuilder);
+        sqlx::query_with(&sql, values)
+            .map(|row: PgRow| {
+                let bytes: Vec<u8> = row.get(1);
+                let type_ = DataType::from(row.get::<i16,_>(2));
+                (row.get(0), DataValue::from_bytes(&bytes, type_))
+            })
+            .fetch_all(&mut *conn)
+            .await?
+    };
+
+    // a grant is effective when all of its conditions are satisfied
+    // access is granted when at least one role allow the procedure and no role of the same api deny it
+    let mut allow = false;
+    for (role_id, deny) in grants {
+        let effective = conditions.iter()
+            .filter(|condition| condition.role_id == role_id)
+            .all(|condition| condition.evaluate(&profiles, params));
+        if effective && deny {
+            return Ok(false);
+        }
+        allow |= effective;
+    }
+
+    Ok(allow)
+}
+
+pub async fn select_access_condition<'c, A>(conn: A, 
+    id: Option<i32>,
+    role_ids: Option<&[Uuid]>,
+    procedure_id: Option<Uuid>
+) -> Result<Vec<AccessConditionSchema>, AuthError>
+where A: Acquire<'c, Database = Postgres>
+{
+    let mut conn = conn.acquire().await?;
+    let mut stmt = Query::select()
+        .columns([
+            RoleAccessCondition::Id,
+            RoleAccessCondition::RoleId,
+            RoleAccessCondition::ProcedureId,
+            RoleAccessCondition::Profile,
+            RoleAccessCondition::Operator,
+            RoleAccessCondition::Param,
+            RoleAccessCondition::Value,
+            RoleAccessCondition::Type
+        ])
+        .from(RoleAccessCondition::Table)
+        .to_owned();
+
+    if let Some(id) = id {
+        stmt = stmt.and_where(Expr::col(RoleAccessCondition::Id).eq(id)).to_owned();
+    }
+    else {
+        if let Some(role_ids) = role_ids {
+            stmt = stmt.and_where(Expr::col(RoleAccessCondition::RoleId).is_in(role_ids.to_vec())).to_owned();
+        }
+        if let Some(procedure_id) = procedure_id {
+            stmt = stmt.and_where(Expr::col(RoleAccessCondition::ProcedureId).eq(procedure_id)).to_owned();
+        }
+    }
+    let (sql, values) = stmt
+        .order_by(RoleAccessCondition::Id, Order::Asc)
+        .build_sqlx(PostgresQueryBuilder);
+
+    let rows = sqlx::query_with(&sql, values)
+        .map(|row: PgRow| {
+            let bytes: Vec<Vec<u8>> = row.get(6);
+            let types: Vec<i16> = row.get(7);
+            AccessConditionSchema {
+                id: row.get(0),
+                role_id: row.get(1),
+                procedure_id: row.get(2),
+                profile: row.get(3),
+                operator: ConditionOperator::from(row.get::<i16,_>(4)),
+                param: row.get(5),
+                values: bytes.iter().zip(types)
+                    .map(|(bytes, type_)| DataValue::from_bytes(bytes, DataType::from(type_)))
+                    .collect()
+            }
+        })
+        .fetch_all(&mut *conn)
+        .await?;
+
+    Ok(rows)
+}
+
+fn condition_values(values: &[DataValue]) -> (Value, Value)
+{
+    let bytes: Vec<Vec<u8>> = values.iter().map(|value| value.to_bytes()).collect();
+    let types: Vec<i16> = values.iter().map(|value| i16::from(value.get_type())).collect();
+    (bytes.into(), types.into())
+}
+
+pub async fn insert_access_condition<'c, A>(conn: A,
+    role_id: Uuid,
+    procedure_id: Uuid,
+    profile: &str,
+    operator: ConditionOperator,
+    values: &[DataValue],
+    param: Option<&str>
+) -> Result<i32, AuthError>
+where A: Acquire<'c, Database = Postgres>
+{
+    let mut conn = conn.acquire().await?;
+    let (bytes, types) = condition_values(values);
+    let (sql, values) = Query::insert()
+        .into_table(RoleAccessCondition::Table)
+        .columns([
+            RoleAccessCondition::RoleId,
+            RoleAccessCondition::ProcedureId,
+            RoleAccessCondition::Profile,
+            RoleAccessCondition::Operator,
+            RoleAccessCondition::Param,
+            RoleAccessCondition::Value,
+            RoleAccessCondition::Type
+        ])
+        .values([
+            role_id.into(),
+            procedure_id.into(),
+            profile.into(),
+            i16::from(operator).into(),
+            param.into(),
+            bytes.into(),
+            types.into()
+        ])
+        .unwrap_or(&mut sea_query::InsertStatement::default())
+        .returning_col(RoleAccessCondition::Id)
+        .build_sqlx(PostgresQueryBuilder);
+
+    let id: i32 = sqlx::query_with(&sql, values)
+        .map(|row: PgRow| row.get(0))
+        .fetch_one(&mut *conn)
+        .await?;
+
+    Ok(id)
+}
+
+pub async fn update_access_condition<'c, A>(conn: A,
+    id: i32,
+    profile: Option<&str>,
+    operator: Option<ConditionOperator>,
+    values: Option<&[DataValue]>,
+    param: Option<Option<&str>>
+) -> Result<(), AuthError>
+where A: Acquire<'c, Database = Postgres>
+{
+    let mut conn = conn.acquire().await?;
+    let mut stmt = Query::update()
+        .table(RoleAccessCondition::Table)
+        .to_owned();
+
+    if let Some(value) = profile {
+        stmt = stmt.value(RoleAccessCondition::Profile, value).to_owned();
+    }
+    if let Some(value) = operator {
+        stmt = stmt.value(RoleAccessCondition::Operator, i16::from(value)).to_owned();
+    }
+    if let Some(value) = values {
+        let (bytes, types) = condition_values(value);
+        stmt = stmt
+            .value(RoleAccessCondition::Value, bytes)
+            .value(RoleAccessCondition::Type, types)
+            .to_owned();
+    }
+    if let Some(value) = param {
+        stmt = stmt.value(RoleAccessCondition::Param, value).to_owned();
+    }
+
+    let (sql, values) = stmt
+        .and_where(Expr::col(RoleAccessCondition::Id).eq(id))
+        .build_sqlx(PostgresQueryBuilder);
+
+    let result = sqlx::query_with(&sql, values)
+        .execute(&mut *conn)
+        .await?;
+    if result.rows_affected() == 0 {
+        return Err(AuthError::not_found("access condition"));
+    }
+
+    Ok(())
+}
+
+pub async fn delete_access_condition<'c, A>(conn: A, 
+    id: i32
+) -> Result<(), AuthError> 
+where A: Acquire<'c, Database = Postgres>
+{
+    let mut conn = conn.acquire().await?;
+    let (sql, values) = Query::delete()
+        .from_table(RoleAccessCondition::Table)
+        .and_where(Expr::col(RoleAccessCondition::Id).eq(id))
+        .build_sqlx(PostgresQueryBuilder);
+
+    let result = sqlx::query_with(&sql, values)
+        .execute(&mut *conn)
+        .await?;
+    if result.rows_affected() == 0 {
+        return Err(AuthError::not_found("access condition"));
+    }
+
+    Ok(())
+}
+
+// deny of the same procedure in an active role of the user for the same api
+// with conditional false only unconditional deny is selected, otherwise only deny with conditions
+fn deny_access(conditional: bool) -> SelectStatement
+{
+    let deny_user_role = Alias::new("deny_user_role");
+    let deny_role = Alias::new("deny_role");
+    let deny_access = Alias::new("deny_access");
+    let deny_condition = Alias::new("deny_condition");
+    let condition = Expr::exists(Query::select()
+        .expr(Expr::val(1))
+        .from_as(RoleAccessCondition::Table, deny_condition.clone())
+        .and_where(Expr::col((deny_condition.clone(), RoleAccessCondition::RoleId)).equals((deny_access.clone(), RoleAccess::RoleId)))
+        .and_where(Expr::col((deny_condition.clone(), RoleAccessCondition::ProcedureId)).equals((deny_access.clone(), RoleAccess::ProcedureId)))
+        .to_owned()
+    );
+    Query::select()
+        .expr(Expr::val(1))
+        .from_subquery(user_role_union(), deny_user_role.clone())
+        .join_as(JoinType::InnerJoin, Role::Table, deny_role.clone(), 
+            Expr::col((deny_user_role.clone(), UserRole::RoleId))
+            .equals((deny_role.clone(), Role::RoleId))
+        )
+        .join_as(JoinType::InnerJoin, RoleAccess::Table, deny_access.clone(), 
+            Expr::col((deny_role.clone(), Role::RoleId))
+            .equals((deny_access.clone(), RoleAccess::RoleId))
+        )
+        .and_where(Expr::col((deny_user_role.clone(), UserRole::UserId)).equals((UserRole::Table, UserRole::UserId)))
+        .and_where(Expr::col((deny_role.clone(), Role::ApiId)).equals((Role::Table, Role::ApiId)))
+        .and_where(Expr::col((deny_role.clone(), Role::Disabled)).eq(false))
+        .and_where(Expr::col((deny_role.clone(), Role::DeletedAt)).is_null())
+        .and_where(Expr::col((deny_access.clone(), RoleAccess::ProcedureId)).equals((RoleAccess::Table, RoleAccess::ProcedureId)))
+        .and_where(Expr::col((deny_access.clone(), RoleAccess::Deny)).eq(true))
+        .and_where(if conditional { condition } else { condition.not() })
+        .to_owned()
+}
+
+pub async fn select_permission<'c, A>(conn: A, 
+    api_id: Uuid
+) -> Result<Vec<PermissionSchema>, AuthError> 
+where A: Acquire<'c, Database = Postgres>
+{
+    let mut conn = conn.acquire().await?;
+    // a grant is conditional when it has conditions or a conditional deny may revoke it
+    let conditional = Query::select()
+        .expr(Expr::val(1))
+        .from(RoleAccessCondition::Table)
+        .and_where(Expr::col((RoleAccessCondition::Table, RoleAccessCondition::RoleId)).equals((RoleAccess::Table, RoleAccess::RoleId)))
+        .and_where(Expr::col((RoleAccessCondition::Table, RoleAccessCondition::ProcedureId)).equals((RoleAccess::Table, RoleAccess::ProcedureId)))
+        .to_owned();
+
+    let (sql, values) = Query::select()
+        .distinct()
+        .columns([
+            (User::Table, User::UserId),
+            (User::Table, User::Name)
+        ])
+        .columns([
+            (Role::Table, Role::RoleId),
+            (Role::Table, Role::Name)
+        ])
+        .columns([
+            (ApiProcedure::Table, ApiProcedure::ProcedureId),
+            (ApiProcedure::Table, ApiProcedure::Name)
+        ])
+        .expr(Expr::exists(conditional).or(Expr::exists(deny_access(true))))
+        .from_subquery(user_role_union(), Alias::new("user_role"))
+        .inner_join(User::Table, 
+            Expr::col((UserRole::Table, UserRole::UserId))
+            .equals((User::Table, User::UserId))
+        )
+        .inner_join(Role::Table, 
+            Expr::col((UserRole::Table, UserRole::RoleId))
+            .equals((Role::Table, Role::RoleId))
+        )
+        .inner_join(RoleAccess::Table, 
+            Expr::col((Role::Table, Role::RoleId))
+            .equals((RoleAccess::Table, RoleAccess::RoleId))
+        )
+        .inner_join(ApiProcedure::Table, 
+            Expr::col((RoleAccess::Table, RoleAccess::ProcedureId))
+            .equals((ApiProcedure::Table, ApiProcedure::ProcedureId))
+            .and(Expr::col((ApiProcedure::Table, ApiProcedure::ApiId)).equals((Role::Table, Role::ApiId)))
+        )
+        .and_where(Expr::col((Role::Table, Role::ApiId)).eq(api_id))
+        .and_where(Expr::col((RoleAccess::Table, RoleAccess::Deny)).eq(false))
+        .and_where(Expr::col((UserRole::Table, UserRole::UserId)).in_subquery(active_user()))
+        .and_where(Expr::col((Role::Table, Role::Disabled)).eq(false))
+        .and_where(Expr::col((Role::Table, Role::DeletedAt)).is_null())
+        .and_where(Expr::col((Role::Table, Role::ApiId)).in_subquery(active_api()))
+        .and_where(Expr::exists(deny_access(false)).not())
+        .order_by((User::Table, User::Name), Order::Asc)
+        .order_by((Role::Table, Role::Name), Order::Asc)
+        .order_by((ApiProcedure::Table, ApiProcedure::Name), Order::Asc)
+        .build_sqlx(PostgresQueryBuilder);
+
+    let rows = sqlx::query_with(&sql, values)
+        .map(|row: PgRow| {
+            PermissionSchema {
+                user_id: row.get(0),
+                user_name: row.get(1),
+                role_id: row.get(2),
+                role_name: row.get(3),
+                procedure_id: row.get(4),
+                procedure_name: row.get(5),
+                conditional: row.get(6)
+            }
+        })
+        .fetch_all(&mut *conn)
+        .await?;
+
+    Ok(rows)
+}
@@ -1,40 +1,137 @@
-use sqlx::{Pool, Row, Error};
+use std::collections::HashMap;
+use sqlx::{Acquire, Row};
 use sqlx::postgres::{Postgres, PgRow};
-use sea_query::{PostgresQueryBuilder, Query, Expr, Order};
+use sqlx::types::chrono::{DateTime, Utc};
+use sea_query::{PostgresQueryBuilder, Query, Expr, Order, SelectStatement, IntoColumnRef};
+use sea_query::extension::postgres::PgExpr;
 use sea_query_binder::SqlxBinder;
 use uuid::Uuid;
+use futures::{Stream, TryStreamExt};
+use async_stream::try_stream;
 
 use crate::schema::api::{Api, ApiProcedure, ApiSchema, ProcedureSchema};
+use crate::schema::secret::Secret;
 use crate::schema::auth_role::{Role, RoleAccess};
 use crate::utility;
+use crate::error::AuthError;
+use crate::operation::{limit_parent, limit_select, order_parent, description, secret};
+use crate::AuthOptions;
 
-pub(crate) async fn select_api(pool: &Pool<Postgres>, 
-    id: Option<Uuid>,
-    ids: Option<&[Uuid]>,
-    name_exact: Option<&str>,
-    name_like: Option<&str>,
-    category: Option<&str>
-) -> Result<Vec<ApiSchema>, Error>
+// ids of api which is not disabled nor deleted
+pub(crate) fn active_api() -> SelectStatement
+{
+    Query::select()
+        .column(Api::ApiId)
+        .from(Api::Table)
+        .and_where(Expr::col(Api::Disabled).eq(false))
+        .and_where(Expr::col(Api::DeletedAt).is_null())
+        .to_owned()
+}
+
+#[derive(Debug, Default, Clone)]
+pub struct ApiFilter {
+    ids: Option<Vec<Uuid>>,
+    names: Option<Vec<String>>,
+    name_like: Option<String>,
+    category_like: Option<String>,
+    updated_since: Option<DateTime<Utc>>
+}
+
+impl ApiFilter {
+
+    pub fn ids(mut self, ids: &[Uuid]) -> Self {
+        self.ids = Some(ids.to_vec());
+        self
+    }
+
+    pub fn names(mut self, names: &[&str]) -> Self {
+        self.names = Some(names.iter().map(|n| n.to_string()).collect());
+        self
+    }
+
+    pub fn name_like(mut self, pattern: &str) -> Self {
+        self.name_like = Some(pattern.to_owned());
+        self
+    }
+
+    pub fn category_like(mut self, pattern: &str) -> Self {
+        self.category_like = Some(pattern.to_owned());
+        self
+    }
+
+    pub fn updated_since(mut self, timestamp: DateTime<Utc>) -> Self {
+        self.updated_since = Some(timestamp);
+        self
+    }
+
+}
+
+// api table filtered by all criteria set in filter, shared by select and count query
+fn filter_api(filter: &ApiFilter, inactive: bool) -> SelectStatement
 {
     let mut stmt = Query::select()
+        .from(Api::Table)
+        .to_owned();
+
+    if let Some(ids) = &filter.ids {
+        stmt = stmt.and_where(Expr::col((Api::Table, Api::ApiId)).is_in(ids.clone())).to_owned();
+    }
+    if let Some(names) = &filter.names {
+        stmt = stmt.and_where(Expr::col((Api::Table, Api::Name)).is_in(names.clone())).to_owned();
+    }
+    if let Some(name) = &filter.name_like {
+        stmt = stmt.and_where(Expr::col((Api::Table, Api::Name)).ilike(name.to_owned())).to_owned();
+    }
+    if let Some(category) = &filter.category_like {
+        stmt = stmt.and_where(Expr::col((Api::Table, Api::Category)).ilike(category.to_owned())).to_owned();
+    }
+    if let Some(timestamp) = filter.updated_since {
+        stmt = stmt.and_where(Expr::col((Api::Table, Api::UpdatedAt)).gte(timestamp)).to_owned();
+    }
+    if !inactive {
+        stmt = stmt
+            .and_where(Expr::col((Api::Table, Api::Disabled)).eq(false))
+            .and_where(Expr::col((Api::Table, Api::DeletedAt)).is_null())
+            .to_owned();
+    }
+
+    stmt
+}
+
+// api select statement with joined procedures and roles ordered by api so rows of the same api stay adjacent, used by stream
+fn api_statement(filter: &ApiFilter, inactive: bool, options: Option<&AuthOptions>) -> SelectStatement
+{
+    let mut stmt = filter_api(filter, inactive);
+    stmt
         .columns([
             (Api::Table, Api::ApiId),
             (Api::Table, Api::Name),
             (Api::Table, Api::Address),
-            (Api::Table, Api::Category),
-            (Api::Table, Api::Description),
-            (Api::Table, Api::Password),
-            (Api::Table, Api::AccessKey)
+            (Api::Table, Api::Category)
         ])
+        .expr(description(options, (Api::Table, Api::Description).into_column_ref()))
+        .expr(secret(options, (Api::Table, Api::Password).into_column_ref(), "''"))
+        .expr(secret(options, (Api::Table, Api::AccessKey).into_column_ref(), "''::bytea"))
         .columns([
             (ApiProcedure::Table, ApiProcedure::ProcedureId),
-            (ApiProcedure::Table, ApiProcedure::Name),
-            (ApiProcedure::Table, ApiProcedure::Description)
+            (ApiProcedure::Table, ApiProcedure::Name)
         ])
+        .expr(description(options, (ApiProcedure::Table, ApiProcedure::Description).into_column_ref()))
         .columns([
             (Role::Table, Role::Name)
         ])
-        .from(Api::Table)
+        .columns([
+            (Api::Table, Api::Disabled),
+            (Api::Table, Api::DeletedAt),
+            (Api::Table, Api::CreatedAt),
+            (Api::Table, Api::UpdatedAt)
+        ])
+        .columns([
+            (ApiProcedure::Table, ApiProcedure::CreatedAt),
+            (ApiProcedure::Table, ApiProcedure::UpdatedAt)
+        ])
+        .column((Api::Table, Api::Version))
+        .column((ApiProcedure::Table, ApiProcedure::Version))
         .left_join(ApiProcedure::Table, 
             Expr::col((Api::Table, Api::ApiId))
             .equals((ApiProcedure::Table, ApiProcedure::ApiId))
@@ -42,98 +139,194 @@ pub(crate) async fn select_api(pool: &Pool<Postgres>,
         .left_join(RoleAccess::Table, 
             Expr::col((ApiProcedure::Table, ApiProcedure::ProcedureId))
             .equals((RoleAccess::Table, RoleAccess::ProcedureId))
+            .and(Expr::col((RoleAccess::Table, RoleAccess::Deny)).eq(false))
         )
         .left_join(Role::Table, 
             Expr::col((RoleAccess::Table, RoleAccess::RoleId))
             .equals((Role::Table, Role::RoleId))
-        )
-        .to_owned();
+        );
 
-    if let Some(id) = id {
-        stmt = stmt.and_where(Expr::col((Api::Table, Api::ApiId)).eq(id)).to_owned();
-    }
-    else if let Some(ids) = ids {
-        stmt = stmt.and_where(Expr::col((Api::Table, Api::ApiId)).is_in(ids.to_vec())).to_owned();
+    let id = (Api::Table, Api::ApiId).into_column_ref();
+    let name = (Api::Table, Api::Name).into_column_ref();
+    limit_parent(&mut stmt, options, id.clone(), name.clone());
+    order_parent(&mut stmt, options, id, name);
+
+    stmt
+        .order_by((ApiProcedure::Table, ApiProcedure::ProcedureId), Order::Asc)
+        .order_by((Role::Table, Role::Name), Order::Asc)
+        .to_owned()
+}
+
+// update api_schema with parent columns and joined procedure and role of a row
+fn api_row(api_schema: &mut ApiSchema, row: &PgRow)
+{
+    let api_id: Uuid = row.get(0);
+    api_schema.id = api_id;
+    api_schema.name = row.get(1);
+    api_schema.address = row.get(2);
+    api_schema.category = row.get(3);
+    api_schema.description = row.get(4);
+    api_schema.password = Secret::new(row.get(5));
+    api_schema.access_key = Secret::new(row.get(6));
+    api_schema.disabled = row.get(11);
+    api_schema.deleted_at = row.get(12);
+    api_schema.created_at = row.get(13);
+    api_schema.updated_at = row.get(14);
+    api_schema.version = row.get(17);
+    // on every new procedure_id found add a procedure to api_schema
+    let procedure_id: Option<Uuid> = row.try_get(7).ok();
+    let last_procedure = api_schema.procedures.last().map(|p| p.id);
+    if let Some(id) = procedure_id && last_procedure != procedure_id {
+        api_schema.procedures.push(ProcedureSchema {
+            id,
+            api_id,
+            name: row.get(8),
+            description: row.get(9),
+            roles: Vec::new(),
+            created_at: row.get(15),
+            updated_at: row.get(16),
+            version: row.get(18)
+        });
     }
-    else if let Some(name) = name_exact {
-        stmt = stmt.and_where(Expr::col((Api::Table, Api::Name)).eq(name.to_owned())).to_owned();
+    // add role to api_schema procedures
+    let role_name: Result<String, _> = row.try_get(10);
+    if let Ok(name) = role_name && let Some(procedure_schema) = api_schema.procedures.last_mut() {
+        procedure_schema.roles.push(name);
     }
-    else {
-        if let Some(name) = name_like {
-            let name_like = String::from("%") + name + "%";
-            stmt = stmt.and_where(Expr::col((Api::Table, Api::Name)).like(name_like)).to_owned();
-        }
-        if let Some(category) = category {
-            let category_like = String::from("%") + category + "%";
-            stmt = stmt.and_where(Expr::col((Api::Table, Api::Category)).like(category_like)).to_owned();
-        }
+}
+
+pub async fn select_api<'c, A>(conn: A, 
+    filter: &ApiFilter,
+    inactive: bool,
+    options: Option<&AuthOptions>
+) -> Result<Vec<ApiSchema>, AuthError>
+where A: Acquire<'c, Database = Postgres>
+{
+    let mut conn = conn.acquire().await?;
+    // select parent rows first so api columns are transferred once per api
+    let mut stmt = filter_api(filter, inactive);
+    stmt
+        .columns([
+            (Api::Table, Api::ApiId),
+            (Api::Table, Api::Name),
+            (Api::Table, Api::Address),
+            (Api::Table, Api::Category)
+        ])
+        .expr(description(options, (Api::Table, Api::Description).into_column_ref()))
+        .expr(secret(options, (Api::Table, Api::Password).into_column_ref(), "''"))
+        .expr(secret(options, (Api::Table, Api::AccessKey).into_column_ref(), "''::bytea"))
+        .columns([
+            (Api::Table, Api::Disabled),
+            (Api::Table, Api::DeletedAt),
+            (Api::Table, Api::CreatedAt),
+            (Api::Table, Api::UpdatedAt),
+            (Api::Table, Api::Version)
+        ]);
+
+    let id = (Api::Table, Api::ApiId).into_column_ref();
+    let name = (Api::Table, Api::Name).into_column_ref();
+    limit_select(&mut stmt, options, id.clone(), name.clone());
+    order_parent(&mut stmt, options, id, name);
+    let (sql, values) = stmt.build_sqlx(PostgresQueryBuilder);
+
+    let mut api_schema_vec: Vec<ApiSchema> = sqlx::query_with(&sql, values)
+        .map(|row: PgRow| {
+            ApiSchema {
+                id: row.get(0),
+                name: row.get(1),
+                address: row.get(2),
+                category: row.get(3),
+                description: row.get(4),
+                password: Secret::new(row.get(5)),
+                access_key: Secret::new(row.get(6)),
+                procedures: Vec::new(),
+                disabled: row.get(7),
+                deleted_at: row.get(8),
+                created_at: row.get(9),
+                updated_at: row.get(10),
+                version: row.get(11)
+            }
+        })
+        .fetch_all(&mut *conn)
+        .await?;
+    if api_schema_vec.is_empty() {
+        return Ok(api_schema_vec);
     }
 
+    // then select procedures of all selected apis in a single query and attach them to its api
+    let api_ids: Vec<Uuid> = api_schema_vec.iter().map(|a| a.id).collect();
+    let mut stmt = filter_procedure(None, None, None, None, None, None);
+    procedure_columns(&mut stmt, options);
     let (sql, values) = stmt
-        .order_by((Api::Table, Api::ApiId), Order::Asc)
+        .and_where(Expr::col((ApiProcedure::Table, ApiProcedure::ApiId)).is_in(api_ids))
         .order_by((ApiProcedure::Table, ApiProcedure::ProcedureId), Order::Asc)
+        .order_by((Role::Table, Role::Name), Order::Asc)
         .build_sqlx(PostgresQueryBuilder);
 
-    let mut last_id: Option<Uuid> = None;
-    let mut last_procedure: Option<Uuid> = None;
-    let mut role_vec: Vec<String> = Vec::new();
-    let mut api_schema_vec: Vec<ApiSchema> = Vec::new();
-
+    let mut proc_schema_vec: Vec<ProcedureSchema> = Vec::new();
     sqlx::query_with(&sql, values)
-        .map(|row: PgRow| {
-            // get last api_schema in api_schema_vec or default
-            let mut api_schema = api_schema_vec.pop().unwrap_or_default();
-            // on every new api_id found update last_id and insert new api_schema to api_schema_vec
+        .map(|row: PgRow| procedure_row(&mut proc_schema_vec, &row))
+        .fetch_all(&mut *conn)
+        .await?;
+
+    let api_index: HashMap<Uuid, usize> = api_schema_vec.iter().enumerate().map(|(i, a)| (a.id, i)).collect();
+    for proc_schema in proc_schema_vec {
+        if let Some(&i) = api_index.get(&proc_schema.api_id) {
+            api_schema_vec[i].procedures.push(proc_schema);
+        }
+    }
+
+    Ok(api_schema_vec)
+}
+
+pub fn stream_api<'c, A>(conn: A, 
+    filter: &ApiFilter,
+    inactive: bool
+) -> impl Stream<Item = Result<ApiSchema, AuthError>> + Send + use<'c, A>
+where A: Acquire<'c, Database = Postgres> + Send + 'c
+{
+    let (sql, values) = api_statement(filter, inactive, None)
+        .build_sqlx(PostgresQueryBuilder);
+
+    try_stream! {
+        let mut conn = conn.acquire().await?;
+        let mut rows = sqlx::query_with(&sql, values)
+            .fetch(&mut *conn);
+        let mut api_schema: Option<ApiSchema> = None;
+        while let Some(row) = rows.try_next().await? {
+            // yield completed api_schema when rows of the next api begin
             let api_id: Uuid = row.get(0);
-            if let Some(value) = last_id {
-                if value != api_id {
-                    api_schema_vec.push(api_schema.clone());
-                    api_schema = ApiSchema::default();
-                    last_procedure = None;
-                    role_vec = Vec::new();
-                }
-            }
-            last_id = Some(api_id);
-            api_schema.id = api_id;
-            api_schema.name = row.get(1);
-            api_schema.address = row.get(2);
-            api_schema.category = row.get(3);
-            api_schema.description = row.get(4);
-            api_schema.password = row.get(5);
-            api_schema.access_key = row.get(6);
-            // on every new procedure_id found add a procedure to api_schema
-            let procedure_id = row.try_get(7).ok();
-            let procedure_name: String = row.try_get(8).unwrap_or_default();
-            if last_procedure == None || last_procedure != procedure_id {
-                if let Some(id) = procedure_id {
-                    api_schema.procedures.push(ProcedureSchema {
-                        id,
-                        api_id,
-                        name: procedure_name.clone(),
-                        description: row.get(9),
-                        roles: Vec::new()
-                    });
-                }
-            }
-            last_procedure = procedure_id;
-            // add role to api_schema procedures
-            let role_name: Result<String, _> = row.try_get(10);
-            if let Ok(name) = role_name {
-                let mut procedure_schema = api_schema.procedures.pop().unwrap_or_default();
-                procedure_schema.roles.push(name.clone());
-                api_schema.procedures.push(procedure_schema);
-                role_vec.push(name);
+            if let Some(schema) = api_schema.take_if(|s| s.id != api_id) {
+                yield schema;
             }
-            // update api_schema_vec with updated api_schema
-            api_schema_vec.push(api_schema);
-        })
-        .fetch_all(pool)
+            api_row(api_schema.get_or_insert_default(), &row);
+        }
+        if let Some(schema) = api_schema {
+            yield schema;
+        }
+    }
+}
+
+pub async fn count_api<'c, A>(conn: A, 
+    filter: &ApiFilter,
+    inactive: bool
+) -> Result<u64, AuthError>
+where A: Acquire<'c, Database = Postgres>
+{
+    let mut conn = conn.acquire().await?;
+    let (sql, values) = filter_api(filter, inactive)
+        .expr(Expr::col((Api::Table, Api::ApiId)).count())
+        .build_sqlx(PostgresQueryBuilder);
+
+    let count: i64 = sqlx::query_with(&sql, values)
+        .map(|row: PgRow| row.get(0))
+        .fetch_one(&mut *conn)
         .await?;
 
-    Ok(api_schema_vec)
+    Ok(count as u64)
 }
 
-pub(crate) async fn insert_api(pool: &Pool<Postgres>, 
+pub async fn insert_api<'c, A>(conn: A, 
     id: Uuid,
     name: &str, 
     address: &str, 
@@ -141,9 +334,11 @@ pub(crate) async fn insert_api(pool: &Pool<Postgres>,
     description: &str,
     password: &str,
     access_key: &[u8]
-) -> Result<Uuid, Error> 
+) -> Result<Uuid, AuthError> 
+where A: Acquire<'c, Database = Postgres>
 {
-    let password_hash = utility::hash_password(&password).or(Err(Error::WorkerCrashed))?;
+    let mut conn = conn.acquire().await?;
+    let password_hash = utility::hash_password(&password).or(Err(AuthError::Hashing))?;
 
     let (sql, values) = Query::insert()
         .into_table(Api::Table)
@@ -169,24 +364,30 @@ pub(crate) async fn insert_api(pool: &Pool<Postgres>,
         .build_sqlx(PostgresQueryBuilder);
 
     sqlx::query_with(&sql, values)
-        .execute(pool)
+        .execute(&mut *conn)
         .await?;
 
     Ok(id)
 }
 
-pub(crate) async fn update_api(pool: &Pool<Postgres>, 
+#[allow(clippy::too_many_arguments)]
+pub async fn update_api<'c, A>(conn: A, 
     id: Uuid, 
     name: Option<&str>, 
     address: Option<&str>, 
     category: Option<&str>, 
     description: Option<&str>,
     password: Option<&str>,
-    access_key: Option<&[u8]>
-) -> Result<(), Error> 
+    access_key: Option<&[u8]>,
+    version: Option<i32>
+) -> Result<u64, AuthError> 
+where A: Acquire<'c, Database = Postgres>
 {
+    let mut conn = conn.acquire().await?;
     let mut stmt = Query::update()
         .table(Api::Table)
+        .value(Api::UpdatedAt, Expr::current_timestamp())
+        .value(Api::Version, Expr::col(Api::Version).add(1))
         .to_owned();
 
     if let Some(value) = name {
@@ -199,7 +400,7 @@ pub(crate) async fn update_api(pool: &Pool<Postgres>,
         stmt = stmt.value(Api::Category, value).to_owned();
     }
     if let Some(value) = password {
-        let password_hash = utility::hash_password(value).or(Err(Error::WorkerCrashed))?;
+        let password_hash = utility::hash_password(value).or(Err(AuthError::Hashing))?;
         stmt = stmt.value(Api::Password, password_hash).to_owned();
     }
     if let Some(value) = description {
@@ -210,56 +411,102 @@ pub(crate) async fn update_api(pool: &Pool<Postgres>,
             .value(Api::AccessKey, value.to_vec())
             .to_owned();
     }
+    // only update when stored version equal to expected version
+    if let Some(value) = version {
+        stmt = stmt.and_where(Expr::col(Api::Version).eq(value)).to_owned();
+    }
 
     let (sql, values) = stmt
         .and_where(Expr::col(Api::ApiId).eq(id))
         .build_sqlx(PostgresQueryBuilder);
 
-    sqlx::query_with(&sql, values)
-        .execute(pool)
+    let result = sqlx::query_with(&sql, values)
+        .execute(&mut *conn)
         .await?;
+    // with expected version zero rows may also mean version conflict, caller tell them apart
+    if result.rows_affected() == 0 && version.is_none() {
+        return Err(AuthError::not_found("api"));
+    }
+
+    Ok(result.rows_affected())
+}
+
+pub async fn update_api_state<'c, A>(conn: A, 
+    id: Uuid, 
+    disabled: Option<bool>, 
+    deleted: Option<bool>
+) -> Result<(), AuthError> 
+where A: Acquire<'c, Database = Postgres>
+{
+    let mut conn = conn.acquire().await?;
+    let mut stmt = Query::update()
+        .table(Api::Table)
+        .value(Api::UpdatedAt, Expr::current_timestamp())
+        .value(Api::Version, Expr::col(Api::Version).add(1))
+        .to_owned();
+
+    if let Some(value) = disabled {
+        stmt = stmt.value(Api::Disabled, value).to_owned();
+    }
+    if let Some(value) = deleted {
+        if value {
+            stmt = stmt.value(Api::DeletedAt, Expr::current_timestamp()).to_owned();
+        } else {
+            stmt = stmt.value(Api::DeletedAt, Option::<DateTime<Utc>>::None).to_owned();
+        }
+    }
+
+    let (sql, values) = stmt
+        .and_where(Expr::col(Api::ApiId).eq(id))
+        .build_sqlx(PostgresQueryBuilder);
+
+    let result = sqlx::query_with(&sql, values)
+        .execute(&mut *conn)
+        .await?;
+    if result.rows_affected() == 0 {
+        return Err(AuthError::not_found("api"));
+    }
 
     Ok(())
 }
 
-pub(crate) async fn delete_api(pool: &Pool<Postgres>, 
+pub async fn delete_api<'c, A>(conn: A, 
     id: Uuid
-) -> Result<(), Error> 
+) -> Result<(), AuthError> 
+where A: Acquire<'c, Database = Postgres>
 {
+    let mut conn = conn.acquire().await?;
     let (sql, values) = Query::delete()
         .from_table(Api::Table)
         .and_where(Expr::col(Api::ApiId).eq(id))
         .build_sqlx(PostgresQueryBuilder);
 
-    sqlx::query_with(&sql, values)
-        .execute(pool)
+    let result = sqlx::query_with(&sql, values)
+        .execute(&mut *conn)
         .await?;
+    if result.rows_affected() == 0 {
+        return Err(AuthError::not_found("api"));
+    }
 
     Ok(())
 }
 
-pub(crate) async fn select_procedure(pool: &Pool<Postgres>, 
+// procedure tables joined and filtered by selected criteria, shared by select and count query
+fn filter_procedure(
     id: Option<Uuid>,
     ids: Option<&[Uuid]>,
     api_id: Option<Uuid>,
     name_exact: Option<&str>,
-    name_like: Option<&str>
-) -> Result<Vec<ProcedureSchema>, Error> 
+    name_like: Option<&str>,
+    updated_since: Option<DateTime<Utc>>
+) -> SelectStatement
 {
     let mut stmt = Query::select()
-        .columns([
-            (ApiProcedure::Table, ApiProcedure::ProcedureId),
-            (ApiProcedure::Table, ApiProcedure::ApiId),
-            (ApiProcedure::Table, ApiProcedure::Name),
-            (ApiProcedure::Table, ApiProcedure::Description)
-        ])
-        .columns([
-            (Role::Table, Role::Name)
-        ])
         .from(ApiProcedure::Table)
         .left_join(RoleAccess::Table, 
             Expr::col((ApiProcedure::Table, ApiProcedure::ProcedureId))
             .equals((RoleAccess::Table, RoleAccess::ProcedureId))
+            .and(Expr::col((RoleAccess::Table, RoleAccess::Deny)).eq(false))
         )
         .left_join(Role::Table, 
             Expr::col((RoleAccess::Table, RoleAccess::RoleId))
@@ -270,70 +517,135 @@ pub(crate) async fn select_procedure(pool: &Pool<Postgres>,
     if let Some(id) = id {
         stmt = stmt.and_where(Expr::col((ApiProcedure::Table, ApiProcedure::ProcedureId)).eq(id)).to_owned();
     }
-    else if let Some(ids) = ids {
+    if let Some(ids) = ids {
         stmt = stmt.and_where(Expr::col((ApiProcedure::Table, ApiProcedure::ProcedureId)).is_in(ids.to_vec())).to_owned();
     }
-    else if let (Some(api_id), Some(name)) = (api_id, name_exact) {
-        stmt = stmt
-            .and_where(Expr::col((ApiProcedure::Table, ApiProcedure::ApiId)).eq(api_id))
-            .and_where(Expr::col((ApiProcedure::Table, ApiProcedure::Name)).eq(name.to_owned()))
-            .to_owned();
+    if let Some(api_id) = api_id {
+        stmt = stmt.and_where(Expr::col((ApiProcedure::Table, ApiProcedure::ApiId)).eq(api_id)).to_owned();
     }
-    else {
-        if let Some(api_id) = api_id {
-            stmt = stmt.and_where(Expr::col((ApiProcedure::Table, ApiProcedure::ApiId)).eq(api_id)).to_owned();
-        }
-        if let Some(name) = name_like {
-            let name_like = String::from("%") + name + "%";
-            stmt = stmt.and_where(Expr::col((ApiProcedure::Table, ApiProcedure::Name)).like(name_like)).to_owned();
-        }
+    if let Some(name) = name_exact {
+        stmt = stmt.and_where(Expr::col((ApiProcedure::Table, ApiProcedure::Name)).eq(name.to_owned())).to_owned();
+    }
+    if let Some(name) = name_like {
+        stmt = stmt.and_where(Expr::col((ApiProcedure::Table, ApiProcedure::Name)).ilike(name.to_owned())).to_owned();
+    }
+
+    if let Some(timestamp) = updated_since {
+        stmt = stmt.and_where(Expr::col((ApiProcedure::Table, ApiProcedure::UpdatedAt)).gte(timestamp)).to_owned();
+    }
+
+    stmt
+}
+
+// procedure select columns with joined role name
+fn procedure_columns(stmt: &mut SelectStatement, options: Option<&AuthOptions>)
+{
+    stmt
+        .columns([
+            (ApiProcedure::Table, ApiProcedure::ProcedureId),
+            (ApiProcedure::Table, ApiProcedure::ApiId),
+            (ApiProcedure::Table, ApiProcedure::Name)
+        ])
+        .expr(description(options, (ApiProcedure::Table, ApiProcedure::Description).into_column_ref()))
+        .columns([
+            (Role::Table, Role::Name)
+        ])
+        .columns([
+            (ApiProcedure::Table, ApiProcedure::CreatedAt),
+            (ApiProcedure::Table, ApiProcedure::UpdatedAt),
+            (ApiProcedure::Table, ApiProcedure::Version)
+        ]);
+}
+
+// add a procedure to proc_schema_vec on every new procedure_id found and add joined role to the last procedure
+fn procedure_row(proc_schema_vec: &mut Vec<ProcedureSchema>, row: &PgRow)
+{
+    let proc_id: Uuid = row.get(0);
+    if proc_schema_vec.last().map(|p| p.id) != Some(proc_id) {
+        proc_schema_vec.push(ProcedureSchema {
+            id: proc_id,
+            api_id: row.get(1),
+            name: row.get(2),
+            description: row.get(3),
+            roles: Vec::new(),
+            created_at: row.get(5),
+            updated_at: row.get(6),
+            version: row.get(7)
+        });
     }
+    let role_name: Result<String, _> = row.try_get(4);
+    if let (Ok(name), Some(proc_schema)) = (role_name, proc_schema_vec.last_mut()) {
+        proc_schema.roles.push(name);
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+pub async fn select_procedure<'c, A>(conn: A, 
+    id: Option<Uuid>,
+    ids: Option<&[Uuid]>,
+    api_id: Option<Uuid>,
+    name_exact: Option<&str>,
+    name_like: Option<&str>,
+    updated_since: Option<DateTime<Utc>>,
+    options: Option<&AuthOptions>
+) -> Result<Vec<ProcedureSchema>, AuthError> 
+where A: Acquire<'c, Database = Postgres>
+{
+    let mut conn = conn.acquire().await?;
+    let mut stmt = filter_procedure(id, ids, api_id, name_exact, name_like, updated_since);
+    procedure_columns(&mut stmt, options);
+
+    let id = (ApiProcedure::Table, ApiProcedure::ProcedureId).into_column_ref();
+    let name = (ApiProcedure::Table, ApiProcedure::Name).into_column_ref();
+    limit_parent(&mut stmt, options, id.clone(), name.clone());
+    order_parent(&mut stmt, options, id, name);
 
     let (sql, values) = stmt
-        .order_by(ApiProcedure::ProcedureId, Order::Asc)
+        .order_by((Role::Table, Role::Name), Order::Asc)
         .build_sqlx(PostgresQueryBuilder);
 
-    let mut last_id: Option<Uuid> = None;
     let mut proc_schema_vec: Vec<ProcedureSchema> = Vec::new();
 
     sqlx::query_with(&sql, values)
-        .map(|row: PgRow| {
-            // get last proc_schema in proc_schema_vec or default
-            let mut proc_schema = proc_schema_vec.pop().unwrap_or_default();
-            // on every new proc_id found update last_id and insert new proc_schema to proc_schema_vec
-            let proc_id: Uuid = row.get(0);
-            if let Some(value) = last_id {
-                if value != proc_id {
-                    proc_schema_vec.push(proc_schema.clone());
-                    proc_schema = ProcedureSchema::default();
-                }
-            }
-            last_id = Some(proc_id);
-            proc_schema.id = proc_id;
-            proc_schema.api_id = row.get(1);
-            proc_schema.name = row.get(2);
-            proc_schema.description = row.get(3);
-            // add role to proc_schema roles
-            let role_name: Result<String, _> = row.try_get(4);
-            if let Ok(name) = role_name {
-                proc_schema.roles.push(name);
-            }
-            // update proc_schema_vec with updated proc_schema
-            proc_schema_vec.push(proc_schema);
-        })
-        .fetch_all(pool)
+        .map(|row: PgRow| procedure_row(&mut proc_schema_vec, &row))
+        .fetch_all(&mut *conn)
         .await?;
 
     Ok(proc_schema_vec)
 }
 
-pub(crate) async fn insert_procedure(pool: &Pool<Postgres>, 
+pub async fn count_procedure<'c, A>(conn: A, 
+    id: Option<Uuid>,
+    ids: Option<&[Uuid]>,
+    api_id: Option<Uuid>,
+    name_exact: Option<&str>,
+    name_like: Option<&str>,
+    updated_since: Option<DateTime<Utc>>
+) -> Result<u64, AuthError>
+where A: Acquire<'c, Database = Postgres>
+{
+    let mut conn = conn.acquire().await?;
+    let (sql, values) = filter_procedure(id, ids, api_id, name_exact, name_like, updated_since)
+        .expr(Expr::col((ApiProcedure::Table, ApiProcedure::ProcedureId)).count_distinct())
+        .build_sqlx(PostgresQueryBuilder);
+
+    let count: i64 = sqlx::query_with(&sql, values)
+        .map(|row: PgRow| row.get(0))
+        .fetch_one(&mut *conn)
+        .await?;
+
+    Ok(count as u64)
+}
+
+pub async fn insert_procedure<'c, A>(conn: A, 
     id: Uuid,
     api_id: Uuid,
     name: &str,
     description: &str
-) -> Result<Uuid, Error> 
+) -> Result<Uuid, AuthError> 
+where A: Acquire<'c, Database = Postgres>
 {
+    let mut conn = conn.acquire().await?;
     let (sql, values) = Query::insert()
         .into_table(ApiProcedure::Table)
         .columns([
@@ -352,20 +664,25 @@ pub(crate) async fn insert_procedure(pool: &Pool<Postgres>,
         .build_sqlx(PostgresQueryBuilder);
 
     sqlx::query_with(&sql, values)
-        .execute(pool)
+        .execute(&mut *conn)
         .await?;
 
     Ok(id)
 }
 
-pub(crate) async fn update_procedure(pool: &Pool<Postgres>, 
+pub async fn update_procedure<'c, A>(conn: A, 
     id: Uuid,
     name: Option<&str>,
-    description: Option<&str>
-) -> Result<(), Error> 
+    description: Option<&str>,
+    version: Option<i32>
+) -> Result<u64, AuthError> 
+where A: Acquire<'c, Database = Postgres>
 {
+    let mut conn = conn.acquire().await?;
     let mut stmt = Query::update()
         .table(ApiProcedure::Table)
+        .value(ApiProcedure::UpdatedAt, Expr::current_timestamp())
+        .value(ApiProcedure::Version, Expr::col(ApiProcedure::Version).add(1))
         .to_owned();
 
     if let Some(value) = name {
@@ -374,30 +691,42 @@ pub(crate) async fn update_procedure(pool: &Pool<Postgres>,
     if let Some(value) = description {
         stmt = stmt.value(ApiProcedure::Description, value).to_owned()
     }
+    // only update when stored version equal to expected version
+    if let Some(value) = version {
+        stmt = stmt.and_where(Expr::col(ApiProcedure::Version).eq(value)).to_owned();
+    }
 
     let (sql, values) = stmt
         .and_where(Expr::col(ApiProcedure::ProcedureId).eq(id))
         .build_sqlx(PostgresQueryBuilder);
 
-    sqlx::query_with(&sql, values)
-        .execute(pool)
+    let result = sqlx::query_with(&sql, values)
+        .execute(&mut *conn)
         .await?;
+    if result.rows_affected() == 0 && version.is_none() {
+        return Err(AuthError::not_found("procedure"));
+    }
 
-    Ok(())
+    Ok(result.rows_affected())
 }
 
-pub(crate) async fn delete_procedure(pool: &Pool<Postgres>, 
+pub async fn delete_procedure<'c, A>(conn: A, 
     id: Uuid
-) -> Result<(), Error> 
+) -> Result<(), AuthError> 
+where A: Acquire<'c, Database = Postgres>
 {
+    let mut conn = conn.acquire().await?;
     let (sql, values) = Query::delete()
         .from_table(ApiProcedure::Table)
         .and_where(Expr::col(ApiProcedure::ProcedureId).eq(id))
         .build_sqlx(PostgresQueryBuilder);
 
-    sqlx::query_with(&sql, values)
-        .execute(pool)
+    let result = sqlx::query_with(&sql, values)
+        .execute(&mut *conn)
         .await?;
+    if result.rows_affected() == 0 {
+        return Err(AuthError::not_found("procedure"));
+    }
 
     Ok(())
 }
@@ -0,0 +1,249 @@
+use sqlx::Acquire;
+use sqlx::postgres::{Postgres, PgConnection};
+use sea_query::{PostgresQueryBuilder, Query, Expr, Cond, DeleteStatement, SelectStatement};
+use sea_query_binder::SqlxBinder;
+use uuid::Uuid;
+
+use crate::schema::api::{Api, ApiProcedure};
+use crate::schema::auth_role::{Role, RoleAccess, RoleAccessCondition};
+use crate::schema::auth_user::{User, UserRole};
+use crate::schema::auth_token::Token;
+use crate::schema::auth_group::{Group, GroupUser, GroupRole};
+use crate::schema::profile::{ProfileRole, ProfileUser};
+use crate::schema::cascade::DeleteSummary;
+use crate::error::AuthError;
+
+async fn execute_delete(conn: &mut PgConnection, 
+    stmt: &DeleteStatement
+) -> Result<u64, AuthError> 
+{
+    let (sql, values) = stmt.build_sqlx(PostgresQueryBuilder);
+    let result = sqlx::query_with(&sql, values)
+        .execute(conn)
+        .await?;
+    Ok(result.rows_affected())
+}
+
+async fn execute_touch(conn: &mut PgConnection, 
+    users: Cond
+) -> Result<(), AuthError> 
+{
+    let (sql, values) = Query::update()
+        .table(User::Table)
+        .value(User::UpdatedAt, Expr::current_timestamp())
+        .cond_where(users)
+        .build_sqlx(PostgresQueryBuilder);
+    sqlx::query_with(&sql, values)
+        .execute(conn)
+        .await?;
+    Ok(())
+}
+
+// users holding the roles directly or through their groups, selected before the role links are deleted
+fn role_user_cond(roles: SelectStatement) -> Cond
+{
+    Cond::any()
+        .add(Expr::col(User::UserId).in_subquery(Query::select()
+            .column(UserRole::UserId)
+            .from(UserRole::Table)
+            .and_where(Expr::col(UserRole::RoleId).in_subquery(roles.clone()))
+            .to_owned()
+        ))
+        .add(Expr::col(User::UserId).in_subquery(Query::select()
+            .column(GroupUser::UserId)
+            .from(GroupUser::Table)
+            .and_where(Expr::col(GroupUser::GroupId).in_subquery(Query::select()
+                .column(GroupRole::GroupId)
+                .from(GroupRole::Table)
+                .and_where(Expr::col(GroupRole::RoleId).in_subquery(roles))
+                .to_owned()
+            ))
+            .to_owned()
+        ))
+}
+
+// delete all rows depend on roles and procedures selected by the subqueries
+async fn delete_role_dependent(conn: &mut PgConnection, 
+    roles: SelectStatement,
+    procedures: Option<SelectStatement>,
+    summary: &mut DeleteSummary
+) -> Result<(), AuthError> 
+{
+    execute_touch(conn, role_user_cond(roles.clone())).await?;
+    let mut condition_cond = Cond::any().add(Expr::col(RoleAccessCondition::RoleId).in_subquery(roles.clone()));
+    let mut access_cond = Cond::any().add(Expr::col(RoleAccess::RoleId).in_subquery(roles.clone()));
+    if let Some(procedures) = procedures {
+        condition_cond = condition_cond.add(Expr::col(RoleAccessCondition::ProcedureId).in_subquery(procedures.clone()));
+        access_cond = access_cond.add(Expr::col(RoleAccess::ProcedureId).in_subquery(procedures));
+    }
+    summary.access_conditions += execute_delete(conn, Query::delete()
+        .from_table(RoleAccessCondition::Table)
+        .cond_where(condition_cond)
+    ).await?;
+    summary.role_access += execute_delete(conn, Query::delete()
+        .from_table(RoleAccess::Table)
+        .cond_where(access_cond)
+    ).await?;
+    summary.user_roles += execute_delete(conn, Query::delete()
+        .from_table(UserRole::Table)
+        .and_where(Expr::col(UserRole::RoleId).in_subquery(roles.clone()))
+    ).await?;
+    summary.group_roles += execute_delete(conn, Query::delete()
+        .from_table(GroupRole::Table)
+        .and_where(Expr::col(GroupRole::RoleId).in_subquery(roles.clone()))
+    ).await?;
+    summary.role_profiles += execute_delete(conn, Query::delete()
+        .from_table(ProfileRole::Table)
+        .and_where(Expr::col(ProfileRole::RoleId).in_subquery(roles))
+    ).await?;
+    Ok(())
+}
+
+pub async fn delete_api_cascade<'c, A>(conn: A, 
+    id: Uuid
+) -> Result<DeleteSummary, AuthError> 
+where A: Acquire<'c, Database = Postgres>
+{
+    let mut tx = conn.begin().await?;
+    let mut summary = DeleteSummary::default();
+
+    let roles = Query::select()
+        .column(Role::RoleId)
+        .from(Role::Table)
+        .and_where(Expr::col(Role::ApiId).eq(id))
+        .to_owned();
+    let procedures = Query::select()
+        .column(ApiProcedure::ProcedureId)
+        .from(ApiProcedure::Table)
+        .and_where(Expr::col(ApiProcedure::ApiId).eq(id))
+        .to_owned();
+    delete_role_dependent(&mut tx, roles, Some(procedures), &mut summary).await?;
+    summary.roles += execute_delete(&mut tx, Query::delete()
+        .from_table(Role::Table)
+        .and_where(Expr::col(Role::ApiId).eq(id))
+    ).await?;
+    summary.procedures += execute_delete(&mut tx, Query::delete()
+        .from_table(ApiProcedure::Table)
+        .and_where(Expr::col(ApiProcedure::ApiId).eq(id))
+    ).await?;
+    summary.apis += execute_delete(&mut tx, Query::delete()
+        .from_table(Api::Table)
+        .and_where(Expr::col(Api::ApiId).eq(id))
+    ).await?;
+
+    tx.commit().await?;
+    Ok(summary)
+}
+
+pub async fn delete_procedure_cascade<'c, A>(conn: A, 
+    id: Uuid
+) -> Result<DeleteSummary, AuthError> 
+where A: Acquire<'c, Database = Postgres>
+{
+    let mut tx = conn.begin().await?;
+    let mut summary = DeleteSummary::default();
+
+    summary.access_conditions += execute_delete(&mut tx, Query::delete()
+        .from_table(RoleAccessCondition::Table)
+        .and_where(Expr::col(RoleAccessCondition::ProcedureId).eq(id))
+    ).await?;
+    summary.role_access += execute_delete(&mut tx, Query::delete()
+        .from_table(RoleAccess::Table)
+        .and_where(Expr::col(RoleAccess::ProcedureId).eq(id))
+    ).await?;
+    summary.procedures += execute_delete(&mut tx, Query::delete()
+        .from_table(ApiProcedure::Table)
+        .and_where(Expr::col(ApiProcedure::ProcedureId).eq(id))
+    ).await?;
+
+    tx.commit().await?;
+    Ok(summary)
+}
+
+pub async fn delete_role_cascade<'c, A>(conn: A, 
+    id: Uuid
+) -> Result<DeleteSummary, AuthError> 
+where A: Acquire<'c, Database = Postgres>
+{
+    let mut tx = conn.begin().await?;
+    let mut summary = DeleteSummary::default();
+
+    let roles = Query::select()
+        .column(Role::RoleId)
+        .from(Role::Table)
+        .and_where(Expr::col(Role::RoleId).eq(id))
+        .to_owned();
+    delete_role_dependent(&mut tx, roles, None, &mut summary).await?;
+    summary.roles += execute_delete(&mut tx, Query::delete()
+        .from_table(Role::Table)
+        .and_where(Expr::col(Role::RoleId).eq(id))
+    ).await?;
+
+    tx.commit().await?;
+    Ok(summary)
+}
+
+pub async fn delete_user_cascade<'c, A>(conn: A, 
+    id: Uuid
+) -> Result<DeleteSummary, AuthError> 
+where A: Acquire<'c, Database = Postgres>
+{
+    let mut tx = conn.begin().await?;
+    let mut summary = DeleteSummary::default();
+
+    summary.user_roles += execute_delete(&mut tx, Query::delete()
+        .from_table(UserRole::Table)
+        .and_where(Expr::col(UserRole::UserId).eq(id))
+    ).await?;
+    summary.group_users += execute_delete(&mut tx, Query::delete()
+        .from_table(GroupUser::Table)
+        .and_where(Expr::col(GroupUser::UserId).eq(id))
+    ).await?;
+    summary.user_profiles += execute_delete(&mut tx, Query::delete()
+        .from_table(ProfileUser::Table)
+        .and_where(Expr::col(ProfileUser::UserId).eq(id))
+    ).await?;
+    summary.tokens += execute_delete(&mut tx, Query::delete()
+        .from_table(Token::Table)
+        .and_where(Expr::col(Token::UserId).eq(id))
+    ).await?;
+    summary.users += execute_delete(&mut tx, Query::delete()
+        .from_table(User::Table)
+        .and_where(Expr::col(User::UserId).eq(id))
+    ).await?;
+
+    tx.commit().await?;
+    Ok(summary)
+}
+
+pub async fn delete_group_cascade<'c, A>(conn: A, 
+    id: Uuid
+) -> Result<DeleteSummary, AuthError> 
+where A: Acquire<'c, Database = Postgres>
+{
+    let mut tx = conn.begin().await?;
+    let mut summary = DeleteSummary::default();
+
+    // members lose the group roles
+    execute_touch(&mut tx, Cond::all().add(Expr::col(User::UserId).in_subquery(Query::select()
+        .column(GroupUser::UserId)
+        .from(GroupUser::Table)
+        .and_where(Expr::col(GroupUser::GroupId).eq(id))
+        .to_owned()
+    ))).await?;
+    summary.group_users += execute_delete(&mut tx, Query::delete()
+        .from_table(GroupUser::Table)
+        .and_where(Expr::col(GroupUser::GroupId).eq(id))
+    ).await?;
+    summary.group_roles += execute_delete(&mut tx, Query::delete()
+        .from_table(GroupRole::Table)
+        .and_where(Expr::col(GroupRole::GroupId).eq(id))
+    ).await?;
+    summary.groups += execute_delete(&mut tx, Query::delete()
+        .from_table(Group::Table)
+        .and_where(Expr::col(Group::GroupId).eq(id))
+    ).await?;
+
+    tx.commit().await?;
+    Ok(summary)
+}
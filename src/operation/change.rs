@@ -0,0 +1,68 @@
+use std::time::Duration;
+use sqlx::Pool;
+use sqlx::postgres::{Postgres, PgListener};
+use futures::Stream;
+use async_stream::stream;
+
+use crate::schema::change::{ChangeEvent, CHANGE_CHANNEL};
+use crate::error::AuthError;
+use crate::cache::AuthCache;
+use crate::operation::group::select_group_user;
+
+const RETRY_MIN: Duration = Duration::from_millis(100);
+const RETRY_MAX: Duration = Duration::from_secs(30);
+
+// listen before returning the stream so no change after this call is missed
+pub async fn listen_change(pool: &Pool<Postgres>)
+    -> Result<impl Stream<Item = ChangeEvent> + Send + use<>, AuthError>
+{
+    let mut listener = PgListener::connect_with(pool).await?;
+    listener.listen(CHANGE_CHANNEL).await?;
+
+    Ok(stream! {
+        let mut retry = RETRY_MIN;
+        loop {
+            match listener.try_recv().await {
+                Ok(Some(notification)) => {
+                    retry = RETRY_MIN;
+                    if let Some(event) = ChangeEvent::from_payload(notification.payload()) {
+                        yield event;
+                    }
+                },
+                // connection lost, listener reconnect and listen again on next receive
+                Ok(None) => yield ChangeEvent::Resync,
+                // reconnect failed, wait with exponential backoff before next attempt
+                Err(_) => {
+                    tokio::time::sleep(retry).await;
+                    retry = (retry * 2).min(RETRY_MAX);
+                }
+            }
+        }
+    })
+}
+
+// remove cached entries affected by a change made on this or other node
+pub(crate) async fn invalidate_change(pool: &Pool<Postgres>, cache: &AuthCache, event: &ChangeEvent)
+{
+    match event {
+        ChangeEvent::Api { id, .. } => cache.remove_api(*id),
+        ChangeEvent::Procedure { api_id, .. } => cache.remove_api(*api_id),
+        ChangeEvent::Role { id, .. } => cache.remove_role(*id),
+        ChangeEvent::RoleAccess { role_id, .. } => cache.remove_role(*role_id),
+        ChangeEvent::UserRole { user_id, .. } => cache.remove_user(*user_id),
+        ChangeEvent::GroupUser { user_id, .. } => cache.remove_user(*user_id),
+        // members of a deleted group are covered by delete events of its group_user rows
+        ChangeEvent::GroupRole { group_id, .. } => {
+            let user_ids = match pool.acquire().await {
+                Ok(mut conn) => select_group_user(&mut conn, *group_id).await,
+                Err(e) => Err(e.into())
+            };
+            match user_ids {
+                Ok(user_ids) => user_ids.into_iter().for_each(|id| cache.remove_user(id)),
+                Err(_) => cache.clear()
+            }
+        },
+        ChangeEvent::Token { .. } => (),
+        ChangeEvent::Resync => cache.clear()
+    }
+}
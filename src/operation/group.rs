@@ -0,0 +1,375 @@
+use std::collections::HashMap;
+use sqlx::{Acquire, Row};
+use sqlx::postgres::{Postgres, PgConnection, PgRow};
+use sea_query::{PostgresQueryBuilder, Query, Expr, Order, Alias, SelectStatement, UnionType, IntoColumnRef};
+use sea_query::extension::postgres::PgExpr;
+use sea_query_binder::SqlxBinder;
+use uuid::Uuid;
+
+use crate::schema::auth_group::{Group, GroupUser, GroupRole, GroupSchema};
+use crate::schema::auth_user::{User, UserRole};
+use crate::error::AuthError;
+use crate::operation::user::touch_user;
+use crate::operation::{limit_select, order_parent, description};
+use crate::AuthOptions;
+
+// union of user roles assigned directly and roles derived from user groups
+// with columns user_id, role_id and group_id which is null for direct user role
+pub(crate) fn user_role_union() -> SelectStatement
+{
+    Query::select()
+        .columns([
+            UserRole::UserId,
+            UserRole::RoleId
+        ])
+        .expr_as(Expr::cust("NULL::uuid"), Alias::new("group_id"))
+        .from(UserRole::Table)
+        .union(UnionType::All, Query::select()
+            .column((GroupUser::Table, GroupUser::UserId))
+            .column((GroupRole::Table, GroupRole::RoleId))
+            .column((GroupUser::Table, GroupUser::GroupId))
+            .from(GroupUser::Table)
+            .inner_join(GroupRole::Table, 
+                Expr::col((GroupUser::Table, GroupUser::GroupId))
+                .equals((GroupRole::Table, GroupRole::GroupId))
+            )
+            .to_owned()
+        )
+        .to_owned()
+}
+
+pub async fn select_group<'c, A>(conn: A, 
+    id: Option<Uuid>,
+    ids: Option<&[Uuid]>,
+    user_id: Option<Uuid>,
+    name_exact: Option<&str>,
+    name_like: Option<&str>,
+    options: Option<&AuthOptions>
+) -> Result<Vec<GroupSchema>, AuthError>
+where A: Acquire<'c, Database = Postgres>
+{
+    let mut conn = conn.acquire().await?;
+    // select group rows first then users and roles separately so members are not multiplied by roles
+    let mut stmt = Query::select()
+        .columns([
+            (Group::Table, Group::GroupId),
+            (Group::Table, Group::Name)
+        ])
+        .expr(description(options, (Group::Table, Group::Description).into_column_ref()))
+        .from(Group::Table)
+        .to_owned();
+
+    if let Some(id) = id {
+        stmt = stmt.and_where(Expr::col((Group::Table, Group::GroupId)).eq(id)).to_owned();
+    }
+    if let Some(ids) = ids {
+        stmt = stmt.and_where(Expr::col((Group::Table, Group::GroupId)).is_in(ids.to_vec())).to_owned();
+    }
+    if let Some(name) = name_exact {
+        stmt = stmt.and_where(Expr::col((Group::Table, Group::Name)).eq(name.to_owned())).to_owned();
+    }
+    if let Some(user_id) = user_id {
+        stmt = stmt.and_where(Expr::col((Group::Table, Group::GroupId)).in_subquery(Query::select()
+            .column(GroupUser::GroupId)
+            .from(GroupUser::Table)
+            .and_where(Expr::col(GroupUser::UserId).eq(user_id))
+            .to_owned()
+        )).to_owned();
+    }
+    if let Some(name) = name_like {
+        stmt = stmt.and_where(Expr::col((Group::Table, Group::Name)).ilike(name.to_owned())).to_owned();
+    }
+
+    let id = (Group::Table, Group::GroupId).into_column_ref();
+    let name = (Group::Table, Group::Name).into_column_ref();
+    limit_select(&mut stmt, options, id.clone(), name.clone());
+    order_parent(&mut stmt, options, id, name);
+    let (sql, values) = stmt.build_sqlx(PostgresQueryBuilder);
+
+    let mut group_schema_vec: Vec<GroupSchema> = sqlx::query_with(&sql, values)
+        .map(|row: PgRow| {
+            GroupSchema {
+                id: row.get(0),
+                name: row.get(1),
+                description: row.get(2),
+                users: Vec::new(),
+                roles: Vec::new()
+            }
+        })
+        .fetch_all(&mut *conn)
+        .await?;
+    if group_schema_vec.is_empty() {
+        return Ok(group_schema_vec);
+    }
+
+    let group_ids: Vec<Uuid> = group_schema_vec.iter().map(|g| g.id).collect();
+    let group_index: HashMap<Uuid, usize> = group_schema_vec.iter().enumerate().map(|(i, g)| (g.id, i)).collect();
+
+    // then select users of all selected groups in a single query
+    let (sql, values) = Query::select()
+        .columns([
+            GroupUser::GroupId,
+            GroupUser::UserId
+        ])
+        .from(GroupUser::Table)
+        .and_where(Expr::col(GroupUser::GroupId).is_in(group_ids.clone()))
+        .order_by(GroupUser::UserId, Order::Asc)
+        .build_sqlx(PostgresQueryBuilder);
+
+    let rows = sqlx::query_with(&sql, values)
+        .fetch_all(&mut *conn)
+        .await?;
+    for row in rows {
+        let group_id: Uuid = row.get(0);
+        if let Some(&i) = group_index.get(&group_id) {
+            group_schema_vec[i].users.push(row.get(1));
+        }
+    }
+
+    // and roles of all selected groups in another single query
+    let (sql, values) = Query::select()
+        .columns([
+            GroupRole::GroupId,
+            GroupRole::RoleId
+        ])
+        .from(GroupRole::Table)
+        .and_where(Expr::col(GroupRole::GroupId).is_in(group_ids))
+        .order_by(GroupRole::RoleId, Order::Asc)
+        .build_sqlx(PostgresQueryBuilder);
+
+    let rows = sqlx::query_with(&sql, values)
+        .fetch_all(&mut *conn)
+        .await?;
+    for row in rows {
+        let group_id: Uuid = row.get(0);
+        if let Some(&i) = group_index.get(&group_id) {
+            group_schema_vec[i].roles.push(row.get(1));
+        }
+    }
+
+    Ok(group_schema_vec)
+}
+
+pub async fn insert_group<'c, A>(conn: A, 
+    id: Uuid,
+    name: &str,
+    description: &str
+) -> Result<Uuid, AuthError> 
+where A: Acquire<'c, Database = Postgres>
+{
+    let mut conn = conn.acquire().await?;
+    let (sql, values) = Query::insert()
+        .into_table(Group::Table)
+        .columns([
+            Group::GroupId,
+            Group::Name,
+            Group::Description
+        ])
+        .values([
+            id.into(),
+            name.into(),
+            description.into()
+        ])
+        .unwrap_or(&mut sea_query::InsertStatement::default())
+        .build_sqlx(PostgresQueryBuilder);
+
+    sqlx::query_with(&sql, values)
+        .execute(&mut *conn)
+        .await?;
+
+    Ok(id)
+}
+
+pub async fn update_group<'c, A>(conn: A, 
+    id: Uuid,
+    name: Option<&str>,
+    description: Option<&str>
+) -> Result<(), AuthError> 
+where A: Acquire<'c, Database = Postgres>
+{
+    let mut conn = conn.acquire().await?;
+    let mut stmt = Query::update()
+        .table(Group::Table)
+        .to_owned();
+
+    if let Some(value) = name {
+        stmt = stmt.value(Group::Name, value).to_owned();
+    }
+    if let Some(value) = description {
+        stmt = stmt.value(Group::Description, value).to_owned();
+    }
+
+    let (sql, values) = stmt
+        .and_where(Expr::col(Group::GroupId).eq(id))
+        .build_sqlx(PostgresQueryBuilder);
+
+    let result = sqlx::query_with(&sql, values)
+        .execute(&mut *conn)
+        .await?;
+    if result.rows_affected() == 0 {
+        return Err(AuthError::not_found("group"));
+    }
+
+    Ok(())
+}
+
+pub async fn delete_group<'c, A>(conn: A, 
+    id: Uuid
+) -> Result<(), AuthError> 
+where A: Acquire<'c, Database = Postgres>
+{
+    let mut conn = conn.acquire().await?;
+    let (sql, values) = Query::delete()
+        .from_table(Group::Table)
+        .and_where(Expr::col(Group::GroupId).eq(id))
+        .build_sqlx(PostgresQueryBuilder);
+
+    let result = sqlx::query_with(&sql, values)
+        .execute(&mut *conn)
+        .await?;
+    if result.rows_affected() == 0 {
+        return Err(AuthError::not_found("group"));
+    }
+
+    Ok(())
+}
+
+pub async fn add_group_user<'c, A>(conn: A, 
+    id: Uuid,
+    user_id: Uuid
+) -> Result<(), AuthError> 
+where A: Acquire<'c, Database = Postgres>
+{
+    let mut conn = conn.acquire().await?;
+    let (sql, values) = Query::insert()
+        .into_table(GroupUser::Table)
+        .columns([
+            GroupUser::GroupId,
+            GroupUser::UserId
+        ])
+        .values([
+            id.into(),
+            user_id.into()
+        ])
+        .unwrap_or(&mut sea_query::InsertStatement::default())
+        .build_sqlx(PostgresQueryBuilder);
+
+    sqlx::query_with(&sql, values)
+        .execute(&mut *conn)
+        .await?;
+    touch_user(&mut conn, user_id).await?;
+
+    Ok(())
+}
+
+pub async fn remove_group_user<'c, A>(conn: A, 
+    id: Uuid,
+    user_id: Uuid
+) -> Result<(), AuthError> 
+where A: Acquire<'c, Database = Postgres>
+{
+    let mut conn = conn.acquire().await?;
+    let (sql, values) = Query::delete()
+        .from_table(GroupUser::Table)
+        .and_where(Expr::col(GroupUser::GroupId).eq(id))
+        .and_where(Expr::col(GroupUser::UserId).eq(user_id))
+        .build_sqlx(PostgresQueryBuilder);
+
+    sqlx::query_with(&sql, values)
+        .execute(&mut *conn)
+        .await?;
+    touch_user(&mut conn, user_id).await?;
+
+    Ok(())
+}
+
+pub async fn add_group_role<'c, A>(conn: A, 
+    id: Uuid,
+    role_id: Uuid
+) -> Result<(), AuthError> 
+where A: Acquire<'c, Database = Postgres>
+{
+    let mut conn = conn.acquire().await?;
+    let (sql, values) = Query::insert()
+        .into_table(GroupRole::Table)
+        .columns([
+            GroupRole::GroupId,
+            GroupRole::RoleId
+        ])
+        .values([
+            id.into(),
+            role_id.into()
+        ])
+        .unwrap_or(&mut sea_query::InsertStatement::default())
+        .build_sqlx(PostgresQueryBuilder);
+
+    sqlx::query_with(&sql, values)
+        .execute(&mut *conn)
+        .await?;
+    touch_group_user(&mut conn, id).await?;
+
+    Ok(())
+}
+
+pub async fn remove_group_role<'c, A>(conn: A, 
+    id: Uuid,
+    role_id: Uuid
+) -> Result<(), AuthError> 
+where A: Acquire<'c, Database = Postgres>
+{
+    let mut conn = conn.acquire().await?;
+    let (sql, values) = Query::delete()
+        .from_table(GroupRole::Table)
+        .and_where(Expr::col(GroupRole::GroupId).eq(id))
+        .and_where(Expr::col(GroupRole::RoleId).eq(role_id))
+        .build_sqlx(PostgresQueryBuilder);
+
+    sqlx::query_with(&sql, values)
+        .execute(&mut *conn)
+        .await?;
+    touch_group_user(&mut conn, id).await?;
+
+    Ok(())
+}
+
+// select users of a group, used to find users affected by a group role change
+pub(crate) async fn select_group_user(conn: &mut PgConnection, 
+    id: Uuid
+) -> Result<Vec<Uuid>, AuthError> 
+{
+    let (sql, values) = Query::select()
+        .column(GroupUser::UserId)
+        .from(GroupUser::Table)
+        .and_where(Expr::col(GroupUser::GroupId).eq(id))
+        .build_sqlx(PostgresQueryBuilder);
+
+    let user_ids = sqlx::query_with(&sql, values)
+        .map(|row: PgRow| row.get(0))
+        .fetch_all(&mut *conn)
+        .await?;
+
+    Ok(user_ids)
+}
+
+// update updated_at timestamp of all users in a group after the group roles changed
+async fn touch_group_user(conn: &mut PgConnection, 
+    id: Uuid
+) -> Result<(), AuthError> 
+{
+    let (sql, values) = Query::update()
+        .table(User::Table)
+        .value(User::UpdatedAt, Expr::current_timestamp())
+        .and_where(Expr::col(User::UserId).in_subquery(Query::select()
+            .column(GroupUser::UserId)
+            .from(GroupUser::Table)
+            .and_where(Expr::col(GroupUser::GroupId).eq(id))
+            .to_owned()
+        ))
+        .build_sqlx(PostgresQueryBuilder);
+
+    sqlx::query_with(&sql, values)
+        .execute(&mut *conn)
+        .await?;
+
+    Ok(())
+}
@@ -1,5 +1,97 @@
-pub(crate) mod api;
-pub(crate) mod role;
-pub(crate) mod user;
-pub(crate) mod profile;
-pub(crate) mod token;
+pub mod api;
+pub mod role;
+pub mod user;
+pub mod profile;
+pub mod token;
+pub mod access;
+pub mod group;
+pub mod cascade;
+pub mod change;
+
+use sea_query::{Expr, Order, ColumnRef, SelectStatement, SimpleExpr};
+use crate::{AuthOptions, OrderOption};
+use crate::schema::page::PageOrder;
+
+// order by list options then by id so joined rows of the same parent stay adjacent
+// keyset page ordering replace list options ordering
+pub(crate) fn order_parent(stmt: &mut SelectStatement, options: Option<&AuthOptions>, id: ColumnRef, name: ColumnRef)
+{
+    if let Some(keyset) = options.and_then(|o| o.keyset.as_ref()) {
+        if let PageOrder::Name = keyset.order {
+            stmt.order_by(name, Order::Asc);
+        }
+        stmt.order_by(id, Order::Asc);
+        return;
+    }
+    let order = options.map(|o| o.order.as_slice()).unwrap_or_default();
+    for option in order {
+        match option {
+            OrderOption::IdAsc => stmt.order_by(id.clone(), Order::Asc),
+            OrderOption::IdDesc => stmt.order_by(id.clone(), Order::Desc),
+            OrderOption::NameAsc => stmt.order_by(name.clone(), Order::Asc),
+            OrderOption::NameDesc => stmt.order_by(name.clone(), Order::Desc)
+        };
+    }
+    stmt.order_by(id, Order::Asc);
+}
+
+// apply limit and offset to parent rows instead of joined rows using filtered and grouped parent id subquery
+pub(crate) fn limit_parent(stmt: &mut SelectStatement, options: Option<&AuthOptions>, id: ColumnRef, name: ColumnRef)
+{
+    if let Some(options) = options {
+        let mut parent = stmt.clone();
+        parent.clear_selects()
+            .column(id.clone())
+            .group_by_col(id.clone())
+            .group_by_col(name.clone());
+        limit_select(&mut parent, Some(options), id.clone(), name.clone());
+        order_parent(&mut parent, Some(options), id.clone(), name);
+        stmt.and_where(Expr::col(id).in_subquery(parent));
+    }
+}
+
+// apply limit and offset directly to statement which select parent rows only
+// keyset page select parent rows after cursor position with one extra row to detect next page
+pub(crate) fn limit_select(stmt: &mut SelectStatement, options: Option<&AuthOptions>, id: ColumnRef, name: ColumnRef)
+{
+    if let Some(options) = options {
+        match &options.keyset {
+            Some(keyset) => {
+                if let Some((last_name, last_id)) = &keyset.after {
+                    let after = match keyset.order {
+                        PageOrder::Id => Expr::col(id).gt(*last_id),
+                        PageOrder::Name => Expr::col(name.clone()).gt(last_name.to_owned())
+                            .or(Expr::col(name).eq(last_name.to_owned())
+                                .and(Expr::col(id).gt(*last_id)))
+                    };
+                    stmt.and_where(after);
+                }
+                stmt.limit(keyset.size as u64 + 1);
+            },
+            None => {
+                stmt.limit(options.limit as u64)
+                    .offset(options.offset as u64);
+            }
+        }
+    }
+}
+
+// select description column or empty text if description omitted by list options
+pub(crate) fn description(options: Option<&AuthOptions>, column: ColumnRef) -> SimpleExpr
+{
+    if options.map(|o| o.with_description).unwrap_or(true) {
+        Expr::col(column).into()
+    } else {
+        Expr::val("").into()
+    }
+}
+
+// select secret column or empty value of the column type if secrets not requested by options
+pub(crate) fn secret(options: Option<&AuthOptions>, column: ColumnRef, empty: &'static str) -> SimpleExpr
+{
+    if options.map(|o| o.with_secrets).unwrap_or(false) {
+        Expr::col(column).into()
+    } else {
+        Expr::cust(empty)
+    }
+}
@@ -1,26 +1,34 @@
 use std::i16;
 
-use sqlx::{Pool, Row, Error};
-use sqlx::postgres::{Postgres, PgRow};
-use sea_query::{PostgresQueryBuilder, Query, Expr, Func};
+use sqlx::{Acquire, Row};
+use sqlx::postgres::{Postgres, PgConnection, PgRow};
+use sqlx::types::chrono::{DateTime, Utc};
+use sea_query::{PostgresQueryBuilder, Query, Expr, Order, Func};
 use sea_query_binder::SqlxBinder;
 use uuid::Uuid;
 
 use crate::schema::profile::{ProfileRole, ProfileUser, RoleProfileSchema, UserProfileSchema, ProfileMode};
+use crate::error::AuthError;
 use rmcs_resource_db::schema::value::{DataValue, DataType};
 
-pub(crate) async fn select_role_profile(pool: &Pool<Postgres>, 
+pub async fn select_role_profile<'c, A>(conn: A, 
     id: Option<i32>,
     role_id: Option<Uuid>,
-) -> Result<Vec<RoleProfileSchema>, Error>
+    role_ids: Option<&[Uuid]>,
+    updated_since: Option<DateTime<Utc>>
+) -> Result<Vec<RoleProfileSchema>, AuthError>
+where A: Acquire<'c, Database = Postgres>
 {
+    let mut conn = conn.acquire().await?;
     let mut stmt = Query::select()
         .columns([
             (ProfileRole::Table, ProfileRole::Id),
             (ProfileRole::Table, ProfileRole::RoleId),
             (ProfileRole::Table, ProfileRole::Name),
             (ProfileRole::Table, ProfileRole::Type),
-            (ProfileRole::Table, ProfileRole::Mode)
+            (ProfileRole::Table, ProfileRole::Mode),
+            (ProfileRole::Table, ProfileRole::CreatedAt),
+            (ProfileRole::Table, ProfileRole::UpdatedAt)
         ])
         .from(ProfileRole::Table)
         .to_owned();
@@ -28,10 +36,19 @@ pub(crate) async fn select_role_profile(pool: &Pool<Postgres>,
     if let Some(id) = id {
         stmt = stmt.and_where(Expr::col((ProfileRole::Table, ProfileRole::Id)).eq(id)).to_owned();
     }
-    else if let Some(role_id) = role_id {
+    if let Some(role_id) = role_id {
         stmt = stmt.and_where(Expr::col((ProfileRole::Table, ProfileRole::RoleId)).eq(role_id)).to_owned();
     }
-    let (sql, values) = stmt.build_sqlx(PostgresQueryBuilder);
+    if let Some(role_ids) = role_ids {
+        stmt = stmt.and_where(Expr::col((ProfileRole::Table, ProfileRole::RoleId)).is_in(role_ids.to_vec())).to_owned();
+    }
+    if let Some(timestamp) = updated_since {
+        stmt = stmt.and_where(Expr::col((ProfileRole::Table, ProfileRole::UpdatedAt)).gte(timestamp)).to_owned();
+    }
+    let (sql, values) = stmt
+        .order_by((ProfileRole::Table, ProfileRole::RoleId), Order::Asc)
+        .order_by((ProfileRole::Table, ProfileRole::Id), Order::Asc)
+        .build_sqlx(PostgresQueryBuilder);
 
     let rows = sqlx::query_with(&sql, values)
         .map(|row: PgRow| {
@@ -40,22 +57,26 @@ pub(crate) async fn select_role_profile(pool: &Pool<Postgres>,
                 role_id: row.get(1),
                 name: row.get(2),
                 value_type: DataType::from(row.get::<i16,_>(3)),
-                mode: ProfileMode::from(row.get::<i16,_>(4))
+                mode: ProfileMode::from(row.get::<i16,_>(4)),
+                created_at: row.get(5),
+                updated_at: row.get(6)
             }
         })
-        .fetch_all(pool)
+        .fetch_all(&mut *conn)
         .await?;
 
     Ok(rows)
 }
 
-pub(crate) async fn insert_role_profile(pool: &Pool<Postgres>,
+pub async fn insert_role_profile<'c, A>(conn: A,
     role_id: Uuid,
     name: &str,
     value_type: DataType,
     mode: ProfileMode
-) -> Result<i32, Error>
+) -> Result<i32, AuthError>
+where A: Acquire<'c, Database = Postgres>
 {
+    let mut conn = conn.acquire().await?;
     let (sql, values) = Query::insert()
         .into_table(ProfileRole::Table)
         .columns([
@@ -74,7 +95,7 @@ pub(crate) async fn insert_role_profile(pool: &Pool<Postgres>,
         .build_sqlx(PostgresQueryBuilder);
 
     sqlx::query_with(&sql, values)
-        .execute(pool)
+        .execute(&mut *conn)
         .await?;
 
     let sql = Query::select()
@@ -83,21 +104,24 @@ pub(crate) async fn insert_role_profile(pool: &Pool<Postgres>,
         .to_string(PostgresQueryBuilder);
     let id: i32 = sqlx::query(&sql)
         .map(|row: PgRow| row.get(0))
-        .fetch_one(pool)
+        .fetch_one(&mut *conn)
         .await?;
 
     Ok(id)
 }
 
-pub(crate) async fn update_role_profile(pool: &Pool<Postgres>,
+pub async fn update_role_profile<'c, A>(conn: A,
     id: i32,
     name: Option<&str>,
     value_type: Option<DataType>,
     mode: Option<ProfileMode>
-) -> Result<(), Error>
+) -> Result<(), AuthError>
+where A: Acquire<'c, Database = Postgres>
 {
+    let mut conn = conn.acquire().await?;
     let mut stmt = Query::update()
         .table(ProfileRole::Table)
+        .value(ProfileRole::UpdatedAt, Expr::current_timestamp())
         .to_owned();
 
     if let Some(value) = name {
@@ -116,34 +140,46 @@ pub(crate) async fn update_role_profile(pool: &Pool<Postgres>,
         .and_where(Expr::col(ProfileRole::Id).eq(id))
         .build_sqlx(PostgresQueryBuilder);
 
-    sqlx::query_with(&sql, values)
-        .execute(pool)
+    let result = sqlx::query_with(&sql, values)
+        .execute(&mut *conn)
         .await?;
+    if result.rows_affected() == 0 {
+        return Err(AuthError::not_found("role profile"));
+    }
 
     Ok(())
 }
 
-pub(crate) async fn delete_role_profile(pool: &Pool<Postgres>, 
+pub async fn delete_role_profile<'c, A>(conn: A, 
     id: i32
-) -> Result<(), Error> 
+) -> Result<(), AuthError> 
+where A: Acquire<'c, Database = Postgres>
 {
+    let mut conn = conn.acquire().await?;
     let (sql, values) = Query::delete()
         .from_table(ProfileRole::Table)
         .and_where(Expr::col(ProfileRole::Id).eq(id))
         .build_sqlx(PostgresQueryBuilder);
 
-    sqlx::query_with(&sql, values)
-        .execute(pool)
+    let result = sqlx::query_with(&sql, values)
+        .execute(&mut *conn)
         .await?;
+    if result.rows_affected() == 0 {
+        return Err(AuthError::not_found("role profile"));
+    }
 
     Ok(())
 }
 
-pub(crate) async fn select_user_profile(pool: &Pool<Postgres>, 
+pub async fn select_user_profile<'c, A>(conn: A, 
     id: Option<i32>,
     user_id: Option<Uuid>,
-) -> Result<Vec<UserProfileSchema>, Error>
+    user_ids: Option<&[Uuid]>,
+    updated_since: Option<DateTime<Utc>>
+) -> Result<Vec<UserProfileSchema>, AuthError>
+where A: Acquire<'c, Database = Postgres>
 {
+    let mut conn = conn.acquire().await?;
     let mut stmt = Query::select()
         .columns([
             (ProfileUser::Table, ProfileUser::Id),
@@ -151,7 +187,9 @@ pub(crate) async fn select_user_profile(pool: &Pool<Postgres>,
             (ProfileUser::Table, ProfileUser::Name),
             (ProfileUser::Table, ProfileUser::Order),
             (ProfileUser::Table, ProfileUser::Value),
-            (ProfileUser::Table, ProfileUser::Type)
+            (ProfileUser::Table, ProfileUser::Type),
+            (ProfileUser::Table, ProfileUser::CreatedAt),
+            (ProfileUser::Table, ProfileUser::UpdatedAt)
         ])
         .from(ProfileUser::Table)
         .to_owned();
@@ -159,10 +197,19 @@ pub(crate) async fn select_user_profile(pool: &Pool<Postgres>,
     if let Some(id) = id {
         stmt = stmt.and_where(Expr::col((ProfileUser::Table, ProfileUser::Id)).eq(id)).to_owned();
     }
-    else if let Some(user_id) = user_id {
+    if let Some(user_id) = user_id {
         stmt = stmt.and_where(Expr::col((ProfileUser::Table, ProfileUser::UserId)).eq(user_id)).to_owned();
     }
-    let (sql, values) = stmt.build_sqlx(PostgresQueryBuilder);
+    if let Some(user_ids) = user_ids {
+        stmt = stmt.and_where(Expr::col((ProfileUser::Table, ProfileUser::UserId)).is_in(user_ids.to_vec())).to_owned();
+    }
+    if let Some(timestamp) = updated_since {
+        stmt = stmt.and_where(Expr::col((ProfileUser::Table, ProfileUser::UpdatedAt)).gte(timestamp)).to_owned();
+    }
+    let (sql, values) = stmt
+        .order_by((ProfileUser::Table, ProfileUser::UserId), Order::Asc)
+        .order_by((ProfileUser::Table, ProfileUser::Id), Order::Asc)
+        .build_sqlx(PostgresQueryBuilder);
 
     let rows = sqlx::query_with(&sql, values)
         .map(|row: PgRow| {
@@ -173,21 +220,25 @@ pub(crate) async fn select_user_profile(pool: &Pool<Postgres>,
                 user_id: row.get(1),
                 name: row.get(2),
                 value: DataValue::from_bytes(bytes, type_),
-                order: row.get(3)
+                order: row.get(3),
+                created_at: row.get(6),
+                updated_at: row.get(7)
             }
         })
-        .fetch_all(pool)
+        .fetch_all(&mut *conn)
         .await?;
 
     Ok(rows)
 }
 
-pub(crate) async fn insert_user_profile(pool: &Pool<Postgres>,
+pub async fn insert_user_profile<'c, A>(conn: A,
     user_id: Uuid,
     name: &str,
     value: DataValue
-) -> Result<i32, Error>
+) -> Result<i32, AuthError>
+where A: Acquire<'c, Database = Postgres>
 {
+    let mut conn = conn.acquire().await?;
     let (sql, values) = Query::select()
         .expr(Func::max(Expr::col(ProfileUser::Order)))
         .and_where(Expr::col(ProfileUser::UserId).eq(user_id))
@@ -196,7 +247,7 @@ pub(crate) async fn insert_user_profile(pool: &Pool<Postgres>,
         .build_sqlx(PostgresQueryBuilder);
     let order: i16 = sqlx::query_with(&sql, values)
         .map(|row: PgRow| row.try_get(0))
-        .fetch_one(pool)
+        .fetch_one(&mut *conn)
         .await
         .unwrap_or(Ok(-1))
         .unwrap_or(-1);
@@ -226,7 +277,7 @@ pub(crate) async fn insert_user_profile(pool: &Pool<Postgres>,
         .build_sqlx(PostgresQueryBuilder);
 
     sqlx::query_with(&sql, values)
-        .execute(pool)
+        .execute(&mut *conn)
         .await?;
 
     let sql = Query::select()
@@ -235,20 +286,23 @@ pub(crate) async fn insert_user_profile(pool: &Pool<Postgres>,
         .to_string(PostgresQueryBuilder);
     let id: i32 = sqlx::query(&sql)
         .map(|row: PgRow| row.get(0))
-        .fetch_one(pool)
+        .fetch_one(&mut *conn)
         .await?;
 
     Ok(id)
 }
 
-pub(crate) async fn update_user_profile(pool: &Pool<Postgres>,
+pub async fn update_user_profile<'c, A>(conn: A,
     id: i32,
     name: Option<&str>,
     value: Option<DataValue>
-) -> Result<(), Error>
+) -> Result<(), AuthError>
+where A: Acquire<'c, Database = Postgres>
 {
+    let mut conn = conn.acquire().await?;
     let mut stmt = Query::update()
         .table(ProfileUser::Table)
+        .value(ProfileUser::UpdatedAt, Expr::current_timestamp())
         .to_owned();
 
     if let Some(value) = name {
@@ -267,62 +321,73 @@ pub(crate) async fn update_user_profile(pool: &Pool<Postgres>,
         .and_where(Expr::col(ProfileUser::Id).eq(id))
         .build_sqlx(PostgresQueryBuilder);
 
-    sqlx::query_with(&sql, values)
-        .execute(pool)
+    let result = sqlx::query_with(&sql, values)
+        .execute(&mut *conn)
         .await?;
+    if result.rows_affected() == 0 {
+        return Err(AuthError::not_found("user profile"));
+    }
 
     Ok(())
 }
 
-pub(crate) async fn delete_user_profile(pool: &Pool<Postgres>, 
+pub async fn delete_user_profile<'c, A>(conn: A, 
     id: i32
-) -> Result<(), Error> 
+) -> Result<(), AuthError> 
+where A: Acquire<'c, Database = Postgres>
 {
+    let mut conn = conn.acquire().await?;
     let (sql, values) = Query::delete()
         .from_table(ProfileUser::Table)
         .and_where(Expr::col(ProfileUser::Id).eq(id))
         .build_sqlx(PostgresQueryBuilder);
 
-    sqlx::query_with(&sql, values)
-        .execute(pool)
+    let result = sqlx::query_with(&sql, values)
+        .execute(&mut *conn)
         .await?;
+    if result.rows_affected() == 0 {
+        return Err(AuthError::not_found("user profile"));
+    }
 
     Ok(())
 }
 
-async fn update_user_profile_order(pool: &Pool<Postgres>,
+async fn update_user_profile_order(conn: &mut PgConnection,
     user_id: Uuid,
     name: &str,
     order: i16,
     order_new: i16
-) -> Result<(), Error>
+) -> Result<(), AuthError>
 {
     let (sql, values) = Query::update()
         .table(ProfileUser::Table)
-        .value(ProfileUser::Order, order_new).to_owned()
+        .value(ProfileUser::Order, order_new)
+        .value(ProfileUser::UpdatedAt, Expr::current_timestamp())
         .and_where(Expr::col(ProfileUser::UserId).eq(user_id))
         .and_where(Expr::col(ProfileUser::Name).eq(name))
         .and_where(Expr::col(ProfileUser::Order).eq(order))
         .build_sqlx(PostgresQueryBuilder);
 
     sqlx::query_with(&sql, values)
-        .execute(pool)
+        .execute(&mut *conn)
         .await?;
 
     Ok(())
 }
 
-pub(crate) async fn swap_user_profile(pool: &Pool<Postgres>, 
+pub async fn swap_user_profile<'c, A>(conn: A, 
     user_id: Uuid,
     name: &str,
     order_1: i16,
     order_2: i16
-) -> Result<(), Error>
+) -> Result<(), AuthError>
+where A: Acquire<'c, Database = Postgres>
 {
+    let mut conn = conn.acquire().await?;
     // change order_1 to max first before change order_2 to correctly swap order
-    update_user_profile_order(pool, user_id, name, order_1, i16::MAX).await?;
-    update_user_profile_order(pool, user_id, name, order_2, order_1).await?;
-    update_user_profile_order(pool, user_id, name, i16::MAX, order_2).await?;
+    update_user_profile_order(&mut conn, user_id, name, order_1, i16::MAX).await?;
+    update_user_profile_order(&mut conn, user_id, name, order_2, order_1).await?;
+    update_user_profile_order(&mut conn, user_id, name, i16::MAX, order_2).await?;
 
     Ok(())
 }
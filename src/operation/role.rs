@@ -1,23 +1,127 @@
-use sqlx::{Pool, Row, Error};
-use sqlx::postgres::{Postgres, PgRow};
-use sea_query::{PostgresQueryBuilder, Query, Expr, Order};
+use std::collections::HashMap;
+use sqlx::{Acquire, Row};
+use sqlx::postgres::{Postgres, PgConnection, PgRow};
+use sqlx::types::chrono::{DateTime, Utc};
+use sea_query::{PostgresQueryBuilder, Query, Expr, Order, Alias, SelectStatement, IntoColumnRef};
+use sea_query::extension::postgres::PgExpr;
 use sea_query_binder::SqlxBinder;
 use uuid::Uuid;
+use futures::{Stream, TryStreamExt};
+use async_stream::try_stream;
 
-use crate::schema::auth_role::{Role, RoleAccess, RoleSchema};
-use crate::schema::api::Api;
+use crate::schema::auth_role::{Role, RoleAccess, RoleAccessCondition, RoleSchema};
+use crate::schema::api::{Api, ApiProcedure};
+use crate::schema::secret::Secret;
 use crate::schema::auth_user::UserRole;
+use crate::schema::profile::ProfileRole;
+use crate::operation::group::user_role_union;
+use crate::error::AuthError;
+use crate::operation::{limit_parent, limit_select, order_parent, secret};
+use crate::AuthOptions;
 
-pub(crate) async fn select_role(pool: &Pool<Postgres>, 
-    id: Option<Uuid>,
-    ids: Option<&[Uuid]>,
+#[derive(Debug, Default, Clone)]
+pub struct RoleFilter {
+    ids: Option<Vec<Uuid>>,
+    names: Option<Vec<String>>,
+    name_like: Option<String>,
     api_id: Option<Uuid>,
+    api_category: Option<String>,
     user_id: Option<Uuid>,
-    name_exact: Option<&str>,
-    name_like: Option<&str>
-) -> Result<Vec<RoleSchema>, Error>
+    updated_since: Option<DateTime<Utc>>
+}
+
+impl RoleFilter {
+
+    pub fn ids(mut self, ids: &[Uuid]) -> Self {
+        self.ids = Some(ids.to_vec());
+        self
+    }
+
+    pub fn names(mut self, names: &[&str]) -> Self {
+        self.names = Some(names.iter().map(|n| n.to_string()).collect());
+        self
+    }
+
+    pub fn name_like(mut self, pattern: &str) -> Self {
+        self.name_like = Some(pattern.to_owned());
+        self
+    }
+
+    pub fn api_id(mut self, api_id: Uuid) -> Self {
+        self.api_id = Some(api_id);
+        self
+    }
+
+    pub fn api_category(mut self, category: &str) -> Self {
+        self.api_category = Some(category.to_owned());
+        self
+    }
+
+    pub fn user_id(mut self, user_id: Uuid) -> Self {
+        self.user_id = Some(user_id);
+        self
+    }
+
+    pub fn updated_since(mut self, timestamp: DateTime<Utc>) -> Self {
+        self.updated_since = Some(timestamp);
+        self
+    }
+
+}
+
+// role table filtered by all criteria set in filter, shared by select and count query
+fn filter_role(filter: &RoleFilter, inactive: bool) -> SelectStatement
 {
     let mut stmt = Query::select()
+        .from(Role::Table)
+        .to_owned();
+
+    if let Some(ids) = &filter.ids {
+        stmt = stmt.and_where(Expr::col((Role::Table, Role::RoleId)).is_in(ids.clone())).to_owned();
+    }
+    if let Some(names) = &filter.names {
+        stmt = stmt.and_where(Expr::col((Role::Table, Role::Name)).is_in(names.clone())).to_owned();
+    }
+    if let Some(name) = &filter.name_like {
+        stmt = stmt.and_where(Expr::col((Role::Table, Role::Name)).ilike(name.to_owned())).to_owned();
+    }
+    if let Some(api_id) = filter.api_id {
+        stmt = stmt.and_where(Expr::col((Role::Table, Role::ApiId)).eq(api_id)).to_owned();
+    }
+    if let Some(category) = &filter.api_category {
+        stmt = stmt.and_where(Expr::col((Role::Table, Role::ApiId)).in_subquery(Query::select()
+            .column(Api::ApiId)
+            .from(Api::Table)
+            .and_where(Expr::col(Api::Category).eq(category.to_owned()))
+            .to_owned()
+        )).to_owned();
+    }
+    if let Some(user_id) = filter.user_id {
+        stmt = stmt.and_where(Expr::col((Role::Table, Role::RoleId)).in_subquery(Query::select()
+            .column((UserRole::Table, UserRole::RoleId))
+            .from_subquery(user_role_union(), Alias::new("user_role"))
+            .and_where(Expr::col((UserRole::Table, UserRole::UserId)).eq(user_id))
+            .to_owned()
+        )).to_owned();
+    }
+    if let Some(timestamp) = filter.updated_since {
+        stmt = stmt.and_where(Expr::col((Role::Table, Role::UpdatedAt)).gte(timestamp)).to_owned();
+    }
+    if !inactive {
+        stmt = stmt
+            .and_where(Expr::col((Role::Table, Role::Disabled)).eq(false))
+            .and_where(Expr::col((Role::Table, Role::DeletedAt)).is_null())
+            .to_owned();
+    }
+
+    stmt
+}
+
+// role select statement with joined procedure access ordered by role so rows of the same role stay adjacent, used by stream
+fn role_statement(filter: &RoleFilter, inactive: bool, options: Option<&AuthOptions>) -> SelectStatement
+{
+    let mut stmt = filter_role(filter, inactive);
+    stmt
         .columns([
             (Role::Table, Role::RoleId),
             (Role::Table, Role::ApiId),
@@ -27,13 +131,18 @@ pub(crate) async fn select_role(pool: &Pool<Postgres>,
             (Role::Table, Role::AccessDuration),
             (Role::Table, Role::RefreshDuration)
         ])
+        .expr(secret(options, (Api::Table, Api::AccessKey).into_column_ref(), "''::bytea"))
         .columns([
-            (Api::Table, Api::AccessKey)
+            (RoleAccess::Table, RoleAccess::ProcedureId),
+            (RoleAccess::Table, RoleAccess::Deny)
         ])
         .columns([
-            (RoleAccess::Table, RoleAccess::ProcedureId)
+            (Role::Table, Role::Disabled),
+            (Role::Table, Role::DeletedAt),
+            (Role::Table, Role::CreatedAt),
+            (Role::Table, Role::UpdatedAt),
+            (Role::Table, Role::Version)
         ])
-        .from(Role::Table)
         .inner_join(Api::Table, 
             Expr::col((Role::Table, Role::ApiId))
             .equals((Api::Table, Api::ApiId))
@@ -41,87 +150,192 @@ pub(crate) async fn select_role(pool: &Pool<Postgres>,
         .left_join(RoleAccess::Table, 
             Expr::col((Role::Table, Role::RoleId))
             .equals((RoleAccess::Table, RoleAccess::RoleId))
-        )
-        .left_join(UserRole::Table,
-            Expr::col((Role::Table, Role::RoleId))
-            .equals((UserRole::Table, UserRole::RoleId))
-        )
-        .to_owned();
+        );
 
-    if let Some(id) = id {
-        stmt = stmt.and_where(Expr::col((Role::Table, Role::RoleId)).eq(id)).to_owned();
-    }
-    else if let Some(ids) = ids {
-        stmt = stmt.and_where(Expr::col((Role::Table, Role::RoleId)).is_in(ids.to_vec())).to_owned();
-    }
-    else if let (Some(api_id), Some(name)) = (api_id, name_exact) {
-        stmt = stmt
-            .and_where(Expr::col((Role::Table, Role::ApiId)).eq(api_id))
-            .and_where(Expr::col((Role::Table, Role::Name)).eq(name.to_owned()))
-            .to_owned();
-    }
-    else {
-        if let Some(api_id) = api_id {
-            stmt = stmt.and_where(Expr::col((Role::Table, Role::ApiId)).eq(api_id)).to_owned();
-        }
-        if let Some(user_id) = user_id {
-            stmt = stmt.and_where(Expr::col((UserRole::Table, UserRole::UserId)).eq(user_id)).to_owned();
-        }
-        if let Some(name) = name_like {
-            let name_like = String::from("%") + name + "%";
-            stmt = stmt.and_where(Expr::col((Api::Table, Api::Name)).like(name_like)).to_owned();
+    let id = (Role::Table, Role::RoleId).into_column_ref();
+    let name = (Role::Table, Role::Name).into_column_ref();
+    limit_parent(&mut stmt, options, id.clone(), name.clone());
+    order_parent(&mut stmt, options, id, name);
+
+    stmt
+        .order_by((RoleAccess::Table, RoleAccess::ProcedureId), Order::Asc)
+        .to_owned()
+}
+
+// update role_schema with parent columns and joined allowed or denied procedure of a row
+fn role_row(role_schema: &mut RoleSchema, row: &PgRow)
+{
+    role_schema.id = row.get(0);
+    role_schema.api_id = row.get(1);
+    role_schema.name = row.get(2);
+    role_schema.multi = row.get(3);
+    role_schema.ip_lock = row.get(4);
+    role_schema.access_duration = row.get(5);
+    role_schema.refresh_duration = row.get(6);
+    role_schema.access_key = Secret::new(row.get(7));
+    role_schema.disabled = row.get(10);
+    role_schema.deleted_at = row.get(11);
+    role_schema.created_at = row.get(12);
+    role_schema.updated_at = row.get(13);
+    role_schema.version = row.get(14);
+    let procedure_id: Option<Uuid> = row.try_get(8).ok();
+    if let Some(id) = procedure_id {
+        let deny: bool = row.try_get(9).unwrap_or_default();
+        if deny {
+            role_schema.denied_procedures.push(id);
+        } else {
+            role_schema.procedures.push(id);
         }
     }
+}
 
-    let (sql, values) = stmt
-        .order_by((Role::Table, Role::RoleId), Order::Asc)
-        .order_by((RoleAccess::Table, RoleAccess::ProcedureId), Order::Asc)
-        .build_sqlx(PostgresQueryBuilder);
+pub async fn select_role<'c, A>(conn: A, 
+    filter: &RoleFilter,
+    inactive: bool,
+    options: Option<&AuthOptions>
+) -> Result<Vec<RoleSchema>, AuthError>
+where A: Acquire<'c, Database = Postgres>
+{
+    let mut conn = conn.acquire().await?;
+    // select parent rows first so role columns are transferred once per role
+    let mut stmt = filter_role(filter, inactive);
+    stmt
+        .columns([
+            (Role::Table, Role::RoleId),
+            (Role::Table, Role::ApiId),
+            (Role::Table, Role::Name),
+            (Role::Table, Role::Multi),
+            (Role::Table, Role::IpLock),
+            (Role::Table, Role::AccessDuration),
+            (Role::Table, Role::RefreshDuration)
+        ])
+        .expr(secret(options, (Api::Table, Api::AccessKey).into_column_ref(), "''::bytea"))
+        .columns([
+            (Role::Table, Role::Disabled),
+            (Role::Table, Role::DeletedAt),
+            (Role::Table, Role::CreatedAt),
+            (Role::Table, Role::UpdatedAt),
+            (Role::Table, Role::Version)
+        ])
+        .inner_join(Api::Table, 
+            Expr::col((Role::Table, Role::ApiId))
+            .equals((Api::Table, Api::ApiId))
+        );
 
-    let mut last_id: Option<Uuid> = None;
-    let mut last_procedure: Option<Uuid> = None;
-    let mut role_schema_vec: Vec<RoleSchema> = Vec::new();
+    let id = (Role::Table, Role::RoleId).into_column_ref();
+    let name = (Role::Table, Role::Name).into_column_ref();
+    limit_select(&mut stmt, options, id.clone(), name.clone());
+    order_parent(&mut stmt, options, id, name);
+    let (sql, values) = stmt.build_sqlx(PostgresQueryBuilder);
 
-    sqlx::query_with(&sql, values)
+    let mut role_schema_vec: Vec<RoleSchema> = sqlx::query_with(&sql, values)
         .map(|row: PgRow| {
-            // get last role_schema in role_schema_vec or default
-            let mut role_schema = role_schema_vec.pop().unwrap_or_default();
-            // on every new role_id found update last_id and insert new role_schema to role_schema_vec
-            let role_id: Uuid = row.get(0);
-            if let Some(value) = last_id {
-                if value != role_id {
-                    role_schema_vec.push(role_schema.clone());
-                    role_schema = RoleSchema::default();
-                    last_procedure = None;
-                }
-            }
-            last_id = Some(role_id);
-            role_schema.id = role_id;
-            role_schema.api_id = row.get(1);
-            role_schema.name = row.get(2);
-            role_schema.multi = row.get(3);
-            role_schema.ip_lock = row.get(4);
-            role_schema.access_duration = row.get(5);
-            role_schema.refresh_duration = row.get(6);
-            role_schema.access_key = row.get(7);
-            // on every new procedure_id found add a procedure to role_schema
-            let procedure_id = row.try_get(8).ok();
-            if last_procedure == None || last_procedure != procedure_id {
-                if let Some(id) = procedure_id {
-                    role_schema.procedures.push(id);
-                }
+            RoleSchema {
+                id: row.get(0),
+                api_id: row.get(1),
+                name: row.get(2),
+                multi: row.get(3),
+                ip_lock: row.get(4),
+                access_duration: row.get(5),
+                refresh_duration: row.get(6),
+                access_key: Secret::new(row.get(7)),
+                procedures: Vec::new(),
+                denied_procedures: Vec::new(),
+                disabled: row.get(8),
+                deleted_at: row.get(9),
+                created_at: row.get(10),
+                updated_at: row.get(11),
+                version: row.get(12)
             }
-            last_procedure = procedure_id;
-            // update role_schema_vec with updated role_schema
-            role_schema_vec.push(role_schema);
         })
-        .fetch_all(pool)
+        .fetch_all(&mut *conn)
+        .await?;
+    if role_schema_vec.is_empty() {
+        return Ok(role_schema_vec);
+    }
+
+    // then select allowed and denied procedures of all selected roles in a single query
+    let role_ids: Vec<Uuid> = role_schema_vec.iter().map(|r| r.id).collect();
+    let (sql, values) = Query::select()
+        .columns([
+            RoleAccess::RoleId,
+            RoleAccess::ProcedureId,
+            RoleAccess::Deny
+        ])
+        .from(RoleAccess::Table)
+        .and_where(Expr::col(RoleAccess::RoleId).is_in(role_ids))
+        .order_by(RoleAccess::ProcedureId, Order::Asc)
+        .build_sqlx(PostgresQueryBuilder);
+
+    let role_index: HashMap<Uuid, usize> = role_schema_vec.iter().enumerate().map(|(i, r)| (r.id, i)).collect();
+    let rows = sqlx::query_with(&sql, values)
+        .fetch_all(&mut *conn)
         .await?;
+    for row in rows {
+        let role_id: Uuid = row.get(0);
+        let procedure_id: Uuid = row.get(1);
+        let deny: bool = row.get(2);
+        if let Some(&i) = role_index.get(&role_id) {
+            let role_schema = &mut role_schema_vec[i];
+            if deny {
+                role_schema.denied_procedures.push(procedure_id);
+            } else {
+                role_schema.procedures.push(procedure_id);
+            }
+        }
+    }
 
     Ok(role_schema_vec)
 }
 
-pub(crate) async fn insert_role(pool: &Pool<Postgres>, 
+pub fn stream_role<'c, A>(conn: A, 
+    filter: &RoleFilter,
+    inactive: bool
+) -> impl Stream<Item = Result<RoleSchema, AuthError>> + Send + use<'c, A>
+where A: Acquire<'c, Database = Postgres> + Send + 'c
+{
+    let (sql, values) = role_statement(filter, inactive, None)
+        .build_sqlx(PostgresQueryBuilder);
+
+    try_stream! {
+        let mut conn = conn.acquire().await?;
+        let mut rows = sqlx::query_with(&sql, values)
+            .fetch(&mut *conn);
+        let mut role_schema: Option<RoleSchema> = None;
+        while let Some(row) = rows.try_next().await? {
+            // yield completed role_schema when rows of the next role begin
+            let role_id: Uuid = row.get(0);
+            if let Some(schema) = role_schema.take_if(|s| s.id != role_id) {
+                yield schema;
+            }
+            role_row(role_schema.get_or_insert_default(), &row);
+        }
+        if let Some(schema) = role_schema {
+            yield schema;
+        }
+    }
+}
+
+pub async fn count_role<'c, A>(conn: A, 
+    filter: &RoleFilter,
+    inactive: bool
+) -> Result<u64, AuthError>
+where A: Acquire<'c, Database = Postgres>
+{
+    let mut conn = conn.acquire().await?;
+    let (sql, values) = filter_role(filter, inactive)
+        .expr(Expr::col((Role::Table, Role::RoleId)).count())
+        .build_sqlx(PostgresQueryBuilder);
+
+    let count: i64 = sqlx::query_with(&sql, values)
+        .map(|row: PgRow| row.get(0))
+        .fetch_one(&mut *conn)
+        .await?;
+
+    Ok(count as u64)
+}
+
+pub async fn insert_role<'c, A>(conn: A, 
     id: Uuid,
     api_id: Uuid,
     name: &str, 
@@ -129,8 +343,10 @@ pub(crate) async fn insert_role(pool: &Pool<Postgres>,
     ip_lock: bool, 
     access_duration: i32,
     refresh_duration: i32,
-) -> Result<Uuid, Error> 
+) -> Result<Uuid, AuthError> 
+where A: Acquire<'c, Database = Postgres>
 {
+    let mut conn = conn.acquire().await?;
     let (sql, values) = Query::insert()
         .into_table(Role::Table)
         .columns([
@@ -155,23 +371,29 @@ pub(crate) async fn insert_role(pool: &Pool<Postgres>,
         .build_sqlx(PostgresQueryBuilder);
 
     sqlx::query_with(&sql, values)
-        .execute(pool)
+        .execute(&mut *conn)
         .await?;
 
     Ok(id)
 }
 
-pub(crate) async fn update_role(pool: &Pool<Postgres>, 
+#[allow(clippy::too_many_arguments)]
+pub async fn update_role<'c, A>(conn: A, 
     id: Uuid, 
     name: Option<&str>, 
     multi: Option<bool>, 
     ip_lock: Option<bool>, 
     access_duration: Option<i32>,
-    refresh_duration: Option<i32>
-) -> Result<(), Error> 
+    refresh_duration: Option<i32>,
+    version: Option<i32>
+) -> Result<u64, AuthError> 
+where A: Acquire<'c, Database = Postgres>
 {
+    let mut conn = conn.acquire().await?;
     let mut stmt = Query::update()
         .table(Role::Table)
+        .value(Role::UpdatedAt, Expr::current_timestamp())
+        .value(Role::Version, Expr::col(Role::Version).add(1))
         .to_owned();
 
     if let Some(value) = name {
@@ -189,73 +411,333 @@ pub(crate) async fn update_role(pool: &Pool<Postgres>,
     if let Some(value) = refresh_duration {
         stmt = stmt.value(Role::RefreshDuration, value).to_owned();
     }
+    // only update when stored version equal to expected version
+    if let Some(value) = version {
+        stmt = stmt.and_where(Expr::col(Role::Version).eq(value)).to_owned();
+    }
 
     let (sql, values) = stmt
         .and_where(Expr::col(Role::RoleId).eq(id))
         .build_sqlx(PostgresQueryBuilder);
 
-    sqlx::query_with(&sql, values)
-        .execute(pool)
+    let result = sqlx::query_with(&sql, values)
+        .execute(&mut *conn)
+        .await?;
+    if result.rows_affected() == 0 && version.is_none() {
+        return Err(AuthError::not_found("role"));
+    }
+
+    Ok(result.rows_affected())
+}
+
+pub async fn update_role_state<'c, A>(conn: A, 
+    id: Uuid, 
+    disabled: Option<bool>, 
+    deleted: Option<bool>
+) -> Result<(), AuthError> 
+where A: Acquire<'c, Database = Postgres>
+{
+    let mut conn = conn.acquire().await?;
+    let mut stmt = Query::update()
+        .table(Role::Table)
+        .value(Role::UpdatedAt, Expr::current_timestamp())
+        .value(Role::Version, Expr::col(Role::Version).add(1))
+        .to_owned();
+
+    if let Some(value) = disabled {
+        stmt = stmt.value(Role::Disabled, value).to_owned();
+    }
+    if let Some(value) = deleted {
+        if value {
+            stmt = stmt.value(Role::DeletedAt, Expr::current_timestamp()).to_owned();
+        } else {
+            stmt = stmt.value(Role::DeletedAt, Option::<DateTime<Utc>>::None).to_owned();
+        }
+    }
+
+    let (sql, values) = stmt
+        .and_where(Expr::col(Role::RoleId).eq(id))
+        .build_sqlx(PostgresQueryBuilder);
+
+    let result = sqlx::query_with(&sql, values)
+        .execute(&mut *conn)
         .await?;
+    if result.rows_affected() == 0 {
+        return Err(AuthError::not_found("role"));
+    }
 
     Ok(())
 }
 
-pub(crate) async fn delete_role(pool: &Pool<Postgres>, 
+pub async fn delete_role<'c, A>(conn: A, 
     id: Uuid
-) -> Result<(), Error> 
+) -> Result<(), AuthError> 
+where A: Acquire<'c, Database = Postgres>
 {
+    let mut conn = conn.acquire().await?;
     let (sql, values) = Query::delete()
         .from_table(Role::Table)
         .and_where(Expr::col(Role::RoleId).eq(id))
         .build_sqlx(PostgresQueryBuilder);
 
-    sqlx::query_with(&sql, values)
-        .execute(pool)
+    let result = sqlx::query_with(&sql, values)
+        .execute(&mut *conn)
         .await?;
+    if result.rows_affected() == 0 {
+        return Err(AuthError::not_found("role"));
+    }
 
     Ok(())
 }
 
-pub(crate) async fn add_role_access(pool: &Pool<Postgres>, 
+pub async fn add_role_access<'c, A>(conn: A, 
     id: Uuid,
-    procedure_id: Uuid
-) -> Result<(), Error> 
+    procedure_id: Uuid,
+    deny: bool
+) -> Result<(), AuthError> 
+where A: Acquire<'c, Database = Postgres>
 {
+    let mut conn = conn.acquire().await?;
     let (sql, values) = Query::insert()
         .into_table(RoleAccess::Table)
         .columns([
             RoleAccess::RoleId,
-            RoleAccess::ProcedureId
+            RoleAccess::ProcedureId,
+            RoleAccess::Deny
         ])
         .values([
             id.into(),
-            procedure_id.into()
+            procedure_id.into(),
+            deny.into()
         ])
         .unwrap_or(&mut sea_query::InsertStatement::default())
         .build_sqlx(PostgresQueryBuilder);
 
     sqlx::query_with(&sql, values)
-        .execute(pool)
+        .execute(&mut *conn)
         .await?;
 
+    touch_role(&mut conn, id).await?;
+
     Ok(())
 }
 
-pub(crate) async fn remove_role_access(pool: &Pool<Postgres>, 
+pub async fn remove_role_access<'c, A>(conn: A, 
     id: Uuid,
-    procedure_id: Uuid
-) -> Result<(), Error> 
+    procedure_id: Uuid,
+    deny: bool
+) -> Result<(), AuthError> 
+where A: Acquire<'c, Database = Postgres>
 {
+    let mut conn = conn.acquire().await?;
     let (sql, values) = Query::delete()
         .from_table(RoleAccess::Table)
         .and_where(Expr::col(RoleAccess::RoleId).eq(id))
         .and_where(Expr::col(RoleAccess::ProcedureId).eq(procedure_id))
+        .and_where(Expr::col(RoleAccess::Deny).eq(deny))
+        .build_sqlx(PostgresQueryBuilder);
+
+    sqlx::query_with(&sql, values)
+        .execute(&mut *conn)
+        .await?;
+
+    touch_role(&mut conn, id).await?;
+
+    Ok(())
+}
+
+// update role updated_at timestamp after its access changed, version is left to role columns update
+async fn touch_role(conn: &mut PgConnection, 
+    id: Uuid
+) -> Result<(), AuthError> 
+{
+    let (sql, values) = Query::update()
+        .table(Role::Table)
+        .value(Role::UpdatedAt, Expr::current_timestamp())
+        .and_where(Expr::col(Role::RoleId).eq(id))
         .build_sqlx(PostgresQueryBuilder);
 
     sqlx::query_with(&sql, values)
-        .execute(pool)
+        .execute(&mut *conn)
         .await?;
 
     Ok(())
 }
+
+pub async fn clone_role<'c, A>(conn: A, 
+    source_id: Uuid,
+    id: Uuid,
+    api_id: Uuid,
+    name: &str
+) -> Result<Vec<String>, AuthError> 
+where A: Acquire<'c, Database = Postgres>
+{
+    let mut tx = conn.begin().await?;
+
+    // read flags and durations of source role
+    let (sql, values) = Query::select()
+        .columns([
+            Role::Multi,
+            Role::IpLock,
+            Role::AccessDuration,
+            Role::RefreshDuration
+        ])
+        .from(Role::Table)
+        .and_where(Expr::col(Role::RoleId).eq(source_id))
+        .build_sqlx(PostgresQueryBuilder);
+    let (multi, ip_lock, access_duration, refresh_duration): (bool, bool, i32, i32) = sqlx::query_with(&sql, values)
+        .map(|row: PgRow| (row.get(0), row.get(1), row.get(2), row.get(3)))
+        .fetch_optional(&mut *tx)
+        .await?
+        .ok_or(AuthError::not_found("role"))?;
+
+    let (sql, values) = Query::insert()
+        .into_table(Role::Table)
+        .columns([
+            Role::RoleId,
+            Role::ApiId,
+            Role::Name,
+            Role::Multi,
+            Role::IpLock,
+            Role::AccessDuration,
+            Role::RefreshDuration
+        ])
+        .values([
+            id.into(),
+            api_id.into(),
+            name.into(),
+            multi.into(),
+            ip_lock.into(),
+            access_duration.into(),
+            refresh_duration.into()
+        ])
+        .unwrap_or(&mut sea_query::InsertStatement::default())
+        .build_sqlx(PostgresQueryBuilder);
+
+    sqlx::query_with(&sql, values)
+        .execute(&mut *tx)
+        .await?;
+
+    // copy role profile definitions of source role
+    let (sql, values) = Query::insert()
+        .into_table(ProfileRole::Table)
+        .columns([
+            ProfileRole::RoleId,
+            ProfileRole::Name,
+            ProfileRole::Type,
+            ProfileRole::Mode
+        ])
+        .select_from(Query::select()
+            .expr(Expr::val(id))
+            .columns([
+                ProfileRole::Name,
+                ProfileRole::Type,
+                ProfileRole::Mode
+            ])
+            .from(ProfileRole::Table)
+            .and_where(Expr::col(ProfileRole::RoleId).eq(source_id))
+            .to_owned()
+        )
+        .unwrap_or(&mut sea_query::InsertStatement::default())
+        .build_sqlx(PostgresQueryBuilder);
+
+    sqlx::query_with(&sql, values)
+        .execute(&mut *tx)
+        .await?;
+
+    // get procedure names of source role access and procedures of target api
+    let (sql, values) = Query::select()
+        .column((ApiProcedure::Table, ApiProcedure::ProcedureId))
+        .column((ApiProcedure::Table, ApiProcedure::Name))
+        .column((RoleAccess::Table, RoleAccess::Deny))
+        .from(RoleAccess::Table)
+        .inner_join(ApiProcedure::Table, 
+            Expr::col((RoleAccess::Table, RoleAccess::ProcedureId))
+            .equals((ApiProcedure::Table, ApiProcedure::ProcedureId))
+        )
+        .and_where(Expr::col((RoleAccess::Table, RoleAccess::RoleId)).eq(source_id))
+        .order_by((ApiProcedure::Table, ApiProcedure::Name), Order::Asc)
+        .build_sqlx(PostgresQueryBuilder);
+    let source_procedures: Vec<(Uuid, String, bool)> = sqlx::query_with(&sql, values)
+        .map(|row: PgRow| (row.get(0), row.get(1), row.get(2)))
+        .fetch_all(&mut *tx)
+        .await?;
+
+    let (sql, values) = Query::select()
+        .columns([
+            ApiProcedure::ProcedureId,
+            ApiProcedure::Name
+        ])
+        .from(ApiProcedure::Table)
+        .and_where(Expr::col(ApiProcedure::ApiId).eq(api_id))
+        .build_sqlx(PostgresQueryBuilder);
+    let target_procedures: Vec<(Uuid, String)> = sqlx::query_with(&sql, values)
+        .map(|row: PgRow| (row.get(0), row.get(1)))
+        .fetch_all(&mut *tx)
+        .await?;
+
+    // add access and its conditions to target procedure with the same name as source procedure
+    let mut unmatched: Vec<String> = Vec::new();
+    for (source_procedure_id, procedure_name, deny) in source_procedures {
+        let target = target_procedures.iter().find(|(_, name)| *name == procedure_name);
+        if let Some((procedure_id, _)) = target {
+            let (sql, values) = Query::insert()
+                .into_table(RoleAccess::Table)
+                .columns([
+                    RoleAccess::RoleId,
+                    RoleAccess::ProcedureId,
+                    RoleAccess::Deny
+                ])
+                .values([
+                    id.into(),
+                    (*procedure_id).into(),
+                    deny.into()
+                ])
+                .unwrap_or(&mut sea_query::InsertStatement::default())
+                .build_sqlx(PostgresQueryBuilder);
+
+            sqlx::query_with(&sql, values)
+                .execute(&mut *tx)
+                .await?;
+
+            let (sql, values) = Query::insert()
+                .into_table(RoleAccessCondition::Table)
+                .columns([
+                    RoleAccessCondition::RoleId,
+                    RoleAccessCondition::ProcedureId,
+                    RoleAccessCondition::Profile,
+                    RoleAccessCondition::Operator,
+                    RoleAccessCondition::Param,
+                    RoleAccessCondition::Value,
+                    RoleAccessCondition::Type
+                ])
+                .select_from(Query::select()
+                    .expr(Expr::val(id))
+                    .expr(Expr::val(*procedure_id))
+                    .columns([
+                        RoleAccessCondition::Profile,
+                        RoleAccessCondition::Operator,
+                        RoleAccessCondition::Param,
+                        RoleAccessCondition::Value,
+                        RoleAccessCondition::Type
+                    ])
+                    .from(RoleAccessCondition::Table)
+                    .and_where(Expr::col(RoleAccessCondition::RoleId).eq(source_id))
+                    .and_where(Expr::col(RoleAccessCondition::ProcedureId).eq(source_procedure_id))
+                    .to_owned()
+                )
+                .unwrap_or(&mut sea_query::InsertStatement::default())
+                .build_sqlx(PostgresQueryBuilder);
+
+            sqlx::query_with(&sql, values)
+                .execute(&mut *tx)
+                .await?;
+        } else {
+            unmatched.push(procedure_name);
+        }
+    }
+
+    tx.commit().await?;
+
+    Ok(unmatched)
+}
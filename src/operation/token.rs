@@ -1,82 +1,155 @@
-use sqlx::{Pool, Row, Error};
+use sqlx::{Acquire, Row};
 use sqlx::postgres::{Postgres, PgRow};
 use sqlx::types::chrono::{DateTime, Utc};
-use sea_query::{PostgresQueryBuilder, Query, Expr, Order, Func};
+use sea_query::{PostgresQueryBuilder, Query, Expr, Order, Func, SelectStatement};
 use sea_query_binder::SqlxBinder;
 use uuid::Uuid;
+use futures::{Stream, TryStreamExt};
+use async_stream::try_stream;
 
 use crate::schema::auth_token::{Token, TokenSchema};
+use crate::schema::auth_user::User;
+use crate::operation::user::active_user;
 use crate::utility;
+use crate::error::AuthError;
 
-pub(crate) enum TokenSelector {
+pub enum TokenSelector {
     Access(i32),
     Auth(String),
     User(Uuid)
 }
 
-pub(crate) async fn select_token(pool: &Pool<Postgres>, 
-    selector: TokenSelector
-) -> Result<Vec<TokenSchema>, Error>
+// token table filtered by selector or all tokens if selector not set, shared by select, stream and count query
+fn filter_token(selector: Option<TokenSelector>) -> SelectStatement
 {
     let mut stmt = Query::select()
-        .columns([
-            Token::AccessId,
-            Token::UserId,
-            Token::RefreshToken,
-            Token::AuthToken,
-            Token::Expire,
-            Token::Ip
-        ])
         .from(Token::Table)
         .to_owned();
 
     match selector {
-        TokenSelector::Access(value) => {
+        Some(TokenSelector::Access(value)) => {
             stmt = stmt.and_where(Expr::col(Token::AccessId).eq(value)).to_owned();
         },
-        TokenSelector::Auth(value) => {
+        Some(TokenSelector::Auth(value)) => {
             stmt = stmt.and_where(Expr::col(Token::AuthToken).eq(value)).to_owned();
         },
-        TokenSelector::User(value) => {
+        Some(TokenSelector::User(value)) => {
             stmt = stmt.and_where(Expr::col(Token::UserId).eq(value)).to_owned();
-        }
+        },
+        None => {}
     }
-    let (sql, values) = stmt
+    stmt
+}
+
+// token select statement ordered by access id
+fn token_statement(selector: Option<TokenSelector>) -> SelectStatement
+{
+    filter_token(selector)
+        .columns([
+            Token::AccessId,
+            Token::UserId,
+            Token::RefreshToken,
+            Token::AuthToken,
+            Token::Expire,
+            Token::Ip
+        ])
         .order_by(Token::AccessId, Order::Asc)
+        .to_owned()
+}
+
+fn token_row(row: &PgRow) -> TokenSchema
+{
+    TokenSchema {
+        access_id: row.get(0),
+        user_id: row.get(1),
+        refresh_token: row.get(2),
+        auth_token: row.get(3),
+        expire: row.get(4),
+        ip: row.get(5)
+    }
+}
+
+pub async fn select_token<'c, A>(conn: A, 
+    selector: TokenSelector
+) -> Result<Vec<TokenSchema>, AuthError>
+where A: Acquire<'c, Database = Postgres>
+{
+    let mut conn = conn.acquire().await?;
+    let (sql, values) = token_statement(Some(selector))
         .build_sqlx(PostgresQueryBuilder);
 
     let row = sqlx::query_with(&sql, values)
-        .map(|row: PgRow| {
-            TokenSchema {
-                access_id: row.get(0),
-                user_id: row.get(1),
-                refresh_token: row.get(2),
-                auth_token: row.get(3),
-                expire: row.get(4),
-                ip: row.get(5)
-            }
-        })
-        .fetch_all(pool)
+        .map(|row: PgRow| token_row(&row))
+        .fetch_all(&mut *conn)
         .await?;
 
     Ok(row)
 }
 
-pub(crate) async fn insert_token(pool: &Pool<Postgres>, 
+pub fn stream_token<'c, A>(conn: A, 
+    selector: Option<TokenSelector>
+) -> impl Stream<Item = Result<TokenSchema, AuthError>> + Send + use<'c, A>
+where A: Acquire<'c, Database = Postgres> + Send + 'c
+{
+    let (sql, values) = token_statement(selector)
+        .build_sqlx(PostgresQueryBuilder);
+
+    try_stream! {
+        let mut conn = conn.acquire().await?;
+        let mut rows = sqlx::query_with(&sql, values)
+            .fetch(&mut *conn);
+        while let Some(row) = rows.try_next().await? {
+            yield token_row(&row);
+        }
+    }
+}
+
+pub async fn count_token<'c, A>(conn: A, 
+    selector: TokenSelector
+) -> Result<u64, AuthError>
+where A: Acquire<'c, Database = Postgres>
+{
+    let mut conn = conn.acquire().await?;
+    let (sql, values) = filter_token(Some(selector))
+        .expr(Expr::col(Token::AccessId).count())
+        .build_sqlx(PostgresQueryBuilder);
+
+    let count: i64 = sqlx::query_with(&sql, values)
+        .map(|row: PgRow| row.get(0))
+        .fetch_one(&mut *conn)
+        .await?;
+
+    Ok(count as u64)
+}
+
+pub async fn insert_token<'c, A>(conn: A, 
     user_id: Uuid, 
     auth_token: Option<&str>,
     expire: DateTime<Utc>, 
     ip: &[u8],
     number: u32
-) -> Result<Vec<(i32, String, String)>, Error> 
+) -> Result<Vec<(i32, String, String)>, AuthError> 
+where A: Acquire<'c, Database = Postgres>
 {
+    let mut conn = conn.acquire().await?;
+    // refuse to issue token for disabled or deleted user
+    let (sql, values) = active_user()
+        .and_where(Expr::col(User::UserId).eq(user_id))
+        .build_sqlx(PostgresQueryBuilder);
+    let active = sqlx::query_with(&sql, values)
+        .fetch_optional(&mut *conn)
+        .await?;
+    if active.is_none() {
+        return Err(AuthError::Validation(String::from("user is disabled or deleted")));
+    }
+
     let sql = Query::select()
         .expr(Func::max(Expr::col(Token::AccessId)))
         .from(Token::Table)
         .to_string(PostgresQueryBuilder);
     let mut access_id: i32 = sqlx::query(&sql)
         .map(|row: PgRow| row.try_get(0))
-        .fetch_one(pool)
+        .fetch_one(&mut *conn)
         .await
         .unwrap_or(Ok(0))
         .unwrap_or(0);
@@ -118,19 +191,21 @@ pub(crate) async fn insert_token(pool: &Pool<Postgres>,
     let (sql, values) = stmt.build_sqlx(PostgresQueryBuilder);
 
     sqlx::query_with(&sql, values)
-        .execute(pool)
+        .execute(&mut *conn)
         .await?;
 
     Ok(gens)
 }
 
-pub(crate) async fn update_token(pool: &Pool<Postgres>, 
+pub async fn update_token<'c, A>(conn: A, 
     access_id: Option<i32>,
     auth_token: Option<&str>,
     expire: Option<DateTime<Utc>>, 
     ip: Option<&[u8]>
-) -> Result<(String, String), Error> 
+) -> Result<(String, String), AuthError> 
+where A: Acquire<'c, Database = Postgres>
 {
+    let mut conn = conn.acquire().await?;
     let refresh_token = utility::generate_token_string();
     let (auth_token, flag) = match auth_token {
         Some(value) => (value.to_owned(), true),
@@ -156,19 +231,27 @@ pub(crate) async fn update_token(pool: &Pool<Postgres>,
     } else {
         stmt = stmt.and_where(Expr::col(Token::AuthToken).eq(auth_token.clone())).to_owned();
     }
-    let (sql, values) = stmt.build_sqlx(PostgresQueryBuilder);
+    // refuse to refresh token of disabled or deleted user
+    let (sql, values) = stmt
+        .and_where(Expr::col(Token::UserId).in_subquery(active_user()))
+        .build_sqlx(PostgresQueryBuilder);
 
-    sqlx::query_with(&sql, values)
-        .execute(pool)
+    let result = sqlx::query_with(&sql, values)
+        .execute(&mut *conn)
         .await?;
+    if result.rows_affected() == 0 {
+        return Err(AuthError::not_found("token"));
+    }
 
     Ok((refresh_token, auth_token))
 }
 
-pub(crate) async fn delete_token(pool: &Pool<Postgres>, 
+pub async fn delete_token<'c, A>(conn: A, 
     selector: TokenSelector
-) -> Result<(), Error> 
+) -> Result<(), AuthError> 
+where A: Acquire<'c, Database = Postgres>
 {
+    let mut conn = conn.acquire().await?;
     let mut stmt = Query::delete()
         .from_table(Token::Table)
         .to_owned();
@@ -186,7 +269,7 @@ pub(crate) async fn delete_token(pool: &Pool<Postgres>,
     let (sql, values) = stmt.build_sqlx(PostgresQueryBuilder);
 
     sqlx::query_with(&sql, values)
-        .execute(pool)
+        .execute(&mut *conn)
         .await?;
 
     Ok(())
@@ -1,28 +1,227 @@
-use sqlx::{Pool, Row, Error};
-use sqlx::postgres::{Postgres, PgRow};
-use sea_query::{PostgresQueryBuilder, Query, Expr, Order};
+use std::collections::HashMap;
+use sqlx::{Acquire, Row};
+use sqlx::postgres::{Postgres, PgConnection, PgRow};
+use sqlx::types::chrono::{DateTime, Utc};
+use sea_query::{PostgresQueryBuilder, Query, Expr, Order, NullOrdering, JoinType, Alias, SelectStatement, SimpleExpr, UnionType, IntoColumnRef};
+use sea_query::extension::postgres::PgExpr;
 use sea_query_binder::SqlxBinder;
 use uuid::Uuid;
+use futures::{Stream, TryStreamExt};
+use async_stream::try_stream;
 
-use crate::schema::auth_user::{User, UserRole, UserSchema, UserRoleSchema};
+use crate::schema::auth_user::{User, UserRole, UserSchema, UserRoleSchema, DuplicateContactSchema};
 use crate::schema::auth_role::Role;
 use crate::schema::api::Api;
+use crate::schema::secret::Secret;
+use crate::schema::auth_group::GroupUser;
+use crate::schema::auth_token::Token;
+use crate::schema::profile::ProfileUser;
+use crate::operation::group::user_role_union;
+use crate::operation::profile::select_user_profile;
+use crate::operation::api::active_api;
 use crate::utility;
+use crate::error::AuthError;
+use crate::operation::{limit_parent, limit_select, order_parent, secret};
+use crate::AuthOptions;
 
-pub(crate) async fn select_user(pool: &Pool<Postgres>, 
-    id: Option<Uuid>,
-    ids: Option<&[Uuid]>,
+// ids of user which is not disabled nor deleted
+pub(crate) fn active_user() -> SelectStatement
+{
+    Query::select()
+        .column(User::UserId)
+        .from(User::Table)
+        .and_where(Expr::col(User::Disabled).eq(false))
+        .and_where(Expr::col(User::DeletedAt).is_null())
+        .to_owned()
+}
+
+#[derive(Debug, Default, Clone)]
+pub struct UserFilter {
+    ids: Option<Vec<Uuid>>,
+    names: Option<Vec<String>>,
+    name_like: Option<String>,
+    email: Option<String>,
+    phone: Option<String>,
     api_id: Option<Uuid>,
-    role_id: Option<Uuid>,
-    name_exact: Option<&str>,
-    name_like: Option<&str>
-) -> Result<Vec<UserSchema>, Error>
+    api_category: Option<String>,
+    role_names: Option<Vec<String>>,
+    has_role: Option<Uuid>,
+    lacks_role: Option<Uuid>,
+    has_profile: Option<String>,
+    updated_since: Option<DateTime<Utc>>
+}
+
+impl UserFilter {
+
+    pub fn ids(mut self, ids: &[Uuid]) -> Self {
+        self.ids = Some(ids.to_vec());
+        self
+    }
+
+    pub fn names(mut self, names: &[&str]) -> Self {
+        self.names = Some(names.iter().map(|n| n.to_string()).collect());
+        self
+    }
+
+    pub fn name_like(mut self, pattern: &str) -> Self {
+        self.name_like = Some(pattern.to_owned());
+        self
+    }
+
+    pub fn email(mut self, email: &str) -> Self {
+        self.email = Some(email.to_owned());
+        self
+    }
+
+    pub fn phone(mut self, phone: &str) -> Self {
+        self.phone = Some(phone.to_owned());
+        self
+    }
+
+    pub fn api_id(mut self, api_id: Uuid) -> Self {
+        self.api_id = Some(api_id);
+        self
+    }
+
+    pub fn api_category(mut self, category: &str) -> Self {
+        self.api_category = Some(category.to_owned());
+        self
+    }
+
+    pub fn role_names(mut self, names: &[&str]) -> Self {
+        self.role_names = Some(names.iter().map(|n| n.to_string()).collect());
+        self
+    }
+
+    pub fn has_role(mut self, role_id: Uuid) -> Self {
+        self.has_role = Some(role_id);
+        self
+    }
+
+    pub fn lacks_role(mut self, role_id: Uuid) -> Self {
+        self.lacks_role = Some(role_id);
+        self
+    }
+
+    pub fn has_profile(mut self, name: &str) -> Self {
+        self.has_profile = Some(name.to_owned());
+        self
+    }
+
+    pub fn updated_since(mut self, timestamp: DateTime<Utc>) -> Self {
+        self.updated_since = Some(timestamp);
+        self
+    }
+
+}
+
+// ids of user with active direct or group derived role matching the condition
+fn user_with_role(condition: SimpleExpr) -> SelectStatement
+{
+    Query::select()
+        .column((UserRole::Table, UserRole::UserId))
+        .from_subquery(user_role_union(), Alias::new("user_role"))
+        .inner_join(Role::Table,
+            Expr::col((UserRole::Table, UserRole::RoleId))
+            .equals((Role::Table, Role::RoleId))
+        )
+        .inner_join(Api::Table,
+            Expr::col((Role::Table, Role::ApiId))
+            .equals((Api::Table, Api::ApiId))
+        )
+        .and_where(Expr::col((Role::Table, Role::Disabled)).eq(false))
+        .and_where(Expr::col((Role::Table, Role::DeletedAt)).is_null())
+        .and_where(Expr::col((Api::Table, Api::Disabled)).eq(false))
+        .and_where(Expr::col((Api::Table, Api::DeletedAt)).is_null())
+        .and_where(condition)
+        .to_owned()
+}
+
+// user table filtered by all criteria set in filter, shared by select and count query
+fn filter_user(filter: &UserFilter, inactive: bool) -> SelectStatement
 {
     let mut stmt = Query::select()
+        .from(User::Table)
+        .to_owned();
+
+    if let Some(ids) = &filter.ids {
+        stmt = stmt.and_where(Expr::col((User::Table, User::UserId)).is_in(ids.clone())).to_owned();
+    }
+    if let Some(names) = &filter.names {
+        stmt = stmt.and_where(Expr::col((User::Table, User::Name)).is_in(names.clone())).to_owned();
+    }
+    if let Some(name) = &filter.name_like {
+        stmt = stmt.and_where(Expr::col((User::Table, User::Name)).ilike(name.to_owned())).to_owned();
+    }
+    if let Some(email) = &filter.email {
+        // compare email case insensitive and without surrounding whitespace, empty email never match
+        // repeat the partial index predicate so the planner can use the email index with a generic plan
+        let email = email.trim().to_lowercase();
+        stmt = stmt
+            .and_where(Expr::cust_with_values(r#"lower(trim("user"."email")) = $1"#, [email]))
+            .and_where(Expr::cust(r#"trim("user"."email") <> ''"#))
+            .to_owned();
+    }
+    if let Some(phone) = &filter.phone {
+        // compare phone digits and plus sign only, empty phone never match
+        let phone: String = phone.chars().filter(|c| c.is_ascii_digit() || *c == '+').collect();
+        stmt = stmt
+            .and_where(Expr::cust_with_values(r#"regexp_replace("user"."phone", '[^0-9+]', '', 'g') = $1"#, [phone]))
+            .and_where(Expr::cust(r#"regexp_replace("user"."phone", '[^0-9+]', '', 'g') <> ''"#))
+            .to_owned();
+    }
+    if let Some(api_id) = filter.api_id {
+        let users = user_with_role(Expr::col((Role::Table, Role::ApiId)).eq(api_id));
+        stmt = stmt.and_where(Expr::col((User::Table, User::UserId)).in_subquery(users)).to_owned();
+    }
+    if let Some(category) = &filter.api_category {
+        let users = user_with_role(Expr::col((Api::Table, Api::Category)).eq(category.to_owned()));
+        stmt = stmt.and_where(Expr::col((User::Table, User::UserId)).in_subquery(users)).to_owned();
+    }
+    if let Some(names) = &filter.role_names {
+        let users = user_with_role(Expr::col((Role::Table, Role::Name)).is_in(names.clone()));
+        stmt = stmt.and_where(Expr::col((User::Table, User::UserId)).in_subquery(users)).to_owned();
+    }
+    if let Some(role_id) = filter.has_role {
+        let users = user_with_role(Expr::col((Role::Table, Role::RoleId)).eq(role_id));
+        stmt = stmt.and_where(Expr::col((User::Table, User::UserId)).in_subquery(users)).to_owned();
+    }
+    if let Some(role_id) = filter.lacks_role {
+        let users = user_with_role(Expr::col((Role::Table, Role::RoleId)).eq(role_id));
+        stmt = stmt.and_where(Expr::col((User::Table, User::UserId)).not_in_subquery(users)).to_owned();
+    }
+    if let Some(name) = &filter.has_profile {
+        stmt = stmt.and_where(Expr::col((User::Table, User::UserId)).in_subquery(Query::select()
+            .column(ProfileUser::UserId)
+            .from(ProfileUser::Table)
+            .and_where(Expr::col(ProfileUser::Name).eq(name.to_owned()))
+            .to_owned()
+        )).to_owned();
+    }
+    if let Some(timestamp) = filter.updated_since {
+        stmt = stmt.and_where(Expr::col((User::Table, User::UpdatedAt)).gte(timestamp)).to_owned();
+    }
+    if !inactive {
+        stmt = stmt
+            .and_where(Expr::col((User::Table, User::Disabled)).eq(false))
+            .and_where(Expr::col((User::Table, User::DeletedAt)).is_null())
+            .to_owned();
+    }
+
+    stmt
+}
+
+// user select statement with joined roles ordered by user so rows of the same user stay adjacent, used by stream
+fn user_statement(filter: &UserFilter, inactive: bool, options: Option<&AuthOptions>) -> SelectStatement
+{
+    let mut stmt = filter_user(filter, inactive);
+    stmt
         .columns([
             (User::Table, User::UserId),
-            (User::Table, User::Name),
-            (User::Table, User::Password),
+            (User::Table, User::Name)
+        ])
+        .expr(secret(options, (User::Table, User::Password).into_column_ref(), "''"))
+        .columns([
             (User::Table, User::Email),
             (User::Table, User::Phone)
         ])
@@ -34,103 +233,259 @@ pub(crate) async fn select_user(pool: &Pool<Postgres>,
             (Role::Table, Role::AccessDuration),
             (Role::Table, Role::RefreshDuration)
         ])
+        .expr(secret(options, (Api::Table, Api::AccessKey).into_column_ref(), "''::bytea"))
+        .column((UserRole::Table, GroupUser::GroupId))
         .columns([
-            (Api::Table, Api::AccessKey)
+            (User::Table, User::Disabled),
+            (User::Table, User::DeletedAt),
+            (User::Table, User::CreatedAt),
+            (User::Table, User::UpdatedAt),
+            (User::Table, User::Version)
         ])
-        .from(User::Table)
-        .left_join(UserRole::Table,
+        .join_subquery(JoinType::LeftJoin, user_role_union(), Alias::new("user_role"),
             Expr::col((User::Table, User::UserId))
             .equals((UserRole::Table, UserRole::UserId))
         )
         .left_join(Role::Table,
             Expr::col((UserRole::Table, UserRole::RoleId))
             .equals((Role::Table, Role::RoleId))
+            .and(Expr::col((Role::Table, Role::Disabled)).eq(false))
+            .and(Expr::col((Role::Table, Role::DeletedAt)).is_null())
+            .and(Expr::col((Role::Table, Role::ApiId)).in_subquery(active_api()))
         )
         .left_join(Api::Table,
             Expr::col((Role::Table, Role::ApiId))
             .equals((Api::Table, Api::ApiId))
-        )
-        .to_owned();
+        );
 
-    if let Some(id) = id {
-        stmt = stmt.and_where(Expr::col((User::Table, User::UserId)).eq(id)).to_owned();
-    }
-    else if let Some(ids) = ids {
-        stmt = stmt.and_where(Expr::col((User::Table, User::UserId)).is_in(ids.to_vec())).to_owned();
-    }
-    else if let Some(name) = name_exact {
-        stmt = stmt.and_where(Expr::col((User::Table, User::Name)).eq(name.to_owned())).to_owned();
-    }
-    else {
-        if let Some(api_id) = api_id {
-            stmt = stmt.and_where(Expr::col((Api::Table, Api::ApiId)).eq(api_id)).to_owned();
-        }
-        if let Some(role_id) = role_id {
-            stmt = stmt.and_where(Expr::col((Role::Table, Role::RoleId)).eq(role_id)).to_owned();
-        }
-        if let Some(name) = name_like {
-            let name_like = String::from("%") + name + "%";
-            stmt = stmt.and_where(Expr::col((User::Table, User::Name)).like(name_like)).to_owned();
+    let id = (User::Table, User::UserId).into_column_ref();
+    let name = (User::Table, User::Name).into_column_ref();
+    limit_parent(&mut stmt, options, id.clone(), name.clone());
+    order_parent(&mut stmt, options, id, name);
+
+    stmt
+        .order_by((UserRole::Table, UserRole::RoleId), Order::Asc)
+        .order_by_with_nulls((UserRole::Table, GroupUser::GroupId), Order::Asc, NullOrdering::First)
+        .to_owned()
+}
+
+// update user_schema with parent columns and joined role of a row
+fn user_row(user_schema: &mut UserSchema, row: &PgRow)
+{
+    user_schema.id = row.get(0);
+    user_schema.name = row.get(1);
+    user_schema.password = Secret::new(row.get(2));
+    user_schema.email = row.get(3);
+    user_schema.phone = row.get(4);
+    user_schema.disabled = row.get(13);
+    user_schema.deleted_at = row.get(14);
+    user_schema.created_at = row.get(15);
+    user_schema.updated_at = row.get(16);
+    user_schema.version = row.get(17);
+    // on every new role found add a role to user_schema, direct role come before group derived role
+    let role_name: Option<String> = row.try_get(6).ok();
+    if let Some(name) = role_name {
+        let api_id: Uuid = row.get(5);
+        if !user_schema.roles.iter().any(|r| r.api_id == api_id && r.role == name) {
+            user_schema.roles.push(UserRoleSchema {
+                api_id,
+                role: name,
+                multi: row.get(7),
+                ip_lock: row.get(8),
+                access_duration: row.get(9),
+                refresh_duration: row.get(10),
+                access_key: Secret::new(row.get(11)),
+                group_id: row.get(12)
+            });
         }
     }
+}
 
-    let (sql, values) = stmt
-        .order_by((User::Table, User::UserId), Order::Asc)
-        .order_by((UserRole::Table, UserRole::RoleId), Order::Asc)
-        .build_sqlx(PostgresQueryBuilder);
+pub async fn select_user<'c, A>(conn: A, 
+    filter: &UserFilter,
+    inactive: bool,
+    options: Option<&AuthOptions>
+) -> Result<Vec<UserSchema>, AuthError>
+where A: Acquire<'c, Database = Postgres>
+{
+    let mut conn = conn.acquire().await?;
+    // select parent rows first so user columns are transferred once per user
+    let mut stmt = filter_user(filter, inactive);
+    stmt
+        .columns([
+            (User::Table, User::UserId),
+            (User::Table, User::Name)
+        ])
+        .expr(secret(options, (User::Table, User::Password).into_column_ref(), "''"))
+        .columns([
+            (User::Table, User::Email),
+            (User::Table, User::Phone),
+            (User::Table, User::Disabled),
+            (User::Table, User::DeletedAt),
+            (User::Table, User::CreatedAt),
+            (User::Table, User::UpdatedAt),
+            (User::Table, User::Version)
+        ]);
 
-    let mut last_id: Option<Uuid> = None;
-    let mut user_schema_vec: Vec<UserSchema> = Vec::new();
+    let id = (User::Table, User::UserId).into_column_ref();
+    let name = (User::Table, User::Name).into_column_ref();
+    limit_select(&mut stmt, options, id.clone(), name.clone());
+    order_parent(&mut stmt, options, id, name);
+    let (sql, values) = stmt.build_sqlx(PostgresQueryBuilder);
 
-    sqlx::query_with(&sql, values)
+    let mut user_schema_vec: Vec<UserSchema> = sqlx::query_with(&sql, values)
         .map(|row: PgRow| {
-            // get last user_schema in user_schema_vec or default
-            let mut user_schema = user_schema_vec.pop().unwrap_or_default();
-            // on every new user_id found update last_id and insert new user_schema to user_schema_vec
-            let user_id: Uuid = row.get(0);
-            if let Some(value) = last_id {
-                if value != user_id {
-                    user_schema_vec.push(user_schema.clone());
-                    user_schema = UserSchema::default();
-                }
+            UserSchema {
+                id: row.get(0),
+                name: row.get(1),
+                password: Secret::new(row.get(2)),
+                email: row.get(3),
+                phone: row.get(4),
+                roles: Vec::new(),
+                profiles: Vec::new(),
+                disabled: row.get(5),
+                deleted_at: row.get(6),
+                created_at: row.get(7),
+                updated_at: row.get(8),
+                version: row.get(9)
             }
-            last_id = Some(user_id);
-            user_schema.id = user_id;
-            user_schema.name = row.get(1);
-            user_schema.password = row.get(2);
-            user_schema.email = row.get(3);
-            user_schema.phone = row.get(4);
-            // on every new role_id found add a role to user_schema
-            let role_name = row.try_get(6).ok();
-            if let Some(name) = role_name {
+        })
+        .fetch_all(&mut *conn)
+        .await?;
+    if user_schema_vec.is_empty() {
+        return Ok(user_schema_vec);
+    }
+    let user_index: HashMap<Uuid, usize> = user_schema_vec.iter().enumerate().map(|(i, u)| (u.id, i)).collect();
+
+    // then select active direct and group derived roles of all selected users in a single query
+    let user_ids: Vec<Uuid> = user_schema_vec.iter().map(|u| u.id).collect();
+    let (sql, values) = Query::select()
+        .column((UserRole::Table, UserRole::UserId))
+        .columns([
+            (Role::Table, Role::ApiId),
+            (Role::Table, Role::Name),
+            (Role::Table, Role::Multi),
+            (Role::Table, Role::IpLock),
+            (Role::Table, Role::AccessDuration),
+            (Role::Table, Role::RefreshDuration)
+        ])
+        .expr(secret(options, (Api::Table, Api::AccessKey).into_column_ref(), "''::bytea"))
+        .column((UserRole::Table, GroupUser::GroupId))
+        .from_subquery(user_role_union(), Alias::new("user_role"))
+        .inner_join(Role::Table,
+            Expr::col((UserRole::Table, UserRole::RoleId))
+            .equals((Role::Table, Role::RoleId))
+        )
+        .inner_join(Api::Table,
+            Expr::col((Role::Table, Role::ApiId))
+            .equals((Api::Table, Api::ApiId))
+        )
+        .and_where(Expr::col((UserRole::Table, UserRole::UserId)).is_in(user_ids))
+        .and_where(Expr::col((Role::Table, Role::Disabled)).eq(false))
+        .and_where(Expr::col((Role::Table, Role::DeletedAt)).is_null())
+        .and_where(Expr::col((Api::Table, Api::Disabled)).eq(false))
+        .and_where(Expr::col((Api::Table, Api::DeletedAt)).is_null())
+        .order_by((UserRole::Table, UserRole::RoleId), Order::Asc)
+        .order_by_with_nulls((UserRole::Table, GroupUser::GroupId), Order::Asc, NullOrdering::First)
+        .build_sqlx(PostgresQueryBuilder);
+
+    let rows = sqlx::query_with(&sql, values)
+        .fetch_all(&mut *conn)
+        .await?;
+    for row in rows {
+        let user_id: Uuid = row.get(0);
+        if let Some(&i) = user_index.get(&user_id) {
+            // direct role come before group derived role of the same role
+            let user_schema = &mut user_schema_vec[i];
+            let api_id: Uuid = row.get(1);
+            let role: String = row.get(2);
+            if !user_schema.roles.iter().any(|r| r.api_id == api_id && r.role == role) {
                 user_schema.roles.push(UserRoleSchema {
-                    api_id: row.get(5),
-                    role: name,
-                    multi: row.get(7),
-                    ip_lock: row.get(8),
-                    access_duration: row.get(9),
-                    refresh_duration: row.get(10),
-                    access_key: row.get(11)
+                    api_id,
+                    role,
+                    multi: row.get(3),
+                    ip_lock: row.get(4),
+                    access_duration: row.get(5),
+                    refresh_duration: row.get(6),
+                    access_key: Secret::new(row.get(7)),
+                    group_id: row.get(8)
                 });
             }
-            // update api_schema_vec with updated user_schema
-            user_schema_vec.push(user_schema);
-        })
-        .fetch_all(pool)
-        .await?;
+        }
+    }
+
+    // attach profiles of all selected users using a single query
+    if options.map(|o| o.with_profiles).unwrap_or(false) {
+        let user_ids: Vec<Uuid> = user_schema_vec.iter().map(|u| u.id).collect();
+        let profiles = select_user_profile(&mut *conn, None, None, Some(&user_ids), None).await?;
+        for profile in profiles {
+            if let Some(&i) = user_index.get(&profile.user_id) {
+                user_schema_vec[i].profiles.push(profile);
+            }
+        }
+    }
 
     Ok(user_schema_vec)
 }
 
-pub(crate) async fn insert_user(pool: &Pool<Postgres>, 
+pub fn stream_user<'c, A>(conn: A, 
+    filter: &UserFilter,
+    inactive: bool
+) -> impl Stream<Item = Result<UserSchema, AuthError>> + Send + use<'c, A>
+where A: Acquire<'c, Database = Postgres> + Send + 'c
+{
+    let (sql, values) = user_statement(filter, inactive, None)
+        .build_sqlx(PostgresQueryBuilder);
+
+    try_stream! {
+        let mut conn = conn.acquire().await?;
+        let mut rows = sqlx::query_with(&sql, values)
+            .fetch(&mut *conn);
+        let mut user_schema: Option<UserSchema> = None;
+        while let Some(row) = rows.try_next().await? {
+            // yield completed user_schema when rows of the next user begin
+            let user_id: Uuid = row.get(0);
+            if let Some(schema) = user_schema.take_if(|s| s.id != user_id) {
+                yield schema;
+            }
+            user_row(user_schema.get_or_insert_default(), &row);
+        }
+        if let Some(schema) = user_schema {
+            yield schema;
+        }
+    }
+}
+
+pub async fn count_user<'c, A>(conn: A, 
+    filter: &UserFilter,
+    inactive: bool
+) -> Result<u64, AuthError>
+where A: Acquire<'c, Database = Postgres>
+{
+    let mut conn = conn.acquire().await?;
+    let (sql, values) = filter_user(filter, inactive)
+        .expr(Expr::col((User::Table, User::UserId)).count())
+        .build_sqlx(PostgresQueryBuilder);
+
+    let count: i64 = sqlx::query_with(&sql, values)
+        .map(|row: PgRow| row.get(0))
+        .fetch_one(&mut *conn)
+        .await?;
+
+    Ok(count as u64)
+}
+
+pub async fn insert_user<'c, A>(conn: A, 
     id: Uuid,
     name: &str, 
     email: &str,
     phone: &str,
     password: &str
-) -> Result<Uuid, Error> 
+) -> Result<Uuid, AuthError> 
+where A: Acquire<'c, Database = Postgres>
 {
-    let password_hash = utility::hash_password(&password).or(Err(Error::WorkerCrashed))?;
+    let mut conn = conn.acquire().await?;
+    let password_hash = utility::hash_password(&password).or(Err(AuthError::Hashing))?;
 
     let (sql, values) = Query::insert()
         .into_table(User::Table)
@@ -152,22 +507,27 @@ pub(crate) async fn insert_user(pool: &Pool<Postgres>,
         .build_sqlx(PostgresQueryBuilder);
 
     sqlx::query_with(&sql, values)
-        .execute(pool)
+        .execute(&mut *conn)
         .await?;
 
     Ok(id)
 }
 
-pub(crate) async fn update_user(pool: &Pool<Postgres>, 
+pub async fn update_user<'c, A>(conn: A, 
     id: Uuid, 
     name: Option<&str>, 
     email: Option<&str>,
     phone: Option<&str>,
-    password: Option<&str>
-) -> Result<(), Error> 
+    password: Option<&str>,
+    version: Option<i32>
+) -> Result<u64, AuthError> 
+where A: Acquire<'c, Database = Postgres>
 {
+    let mut conn = conn.acquire().await?;
     let mut stmt = Query::update()
         .table(User::Table)
+        .value(User::UpdatedAt, Expr::current_timestamp())
+        .value(User::Version, Expr::col(User::Version).add(1))
         .to_owned();
 
     if let Some(value) = name {
@@ -180,42 +540,109 @@ pub(crate) async fn update_user(pool: &Pool<Postgres>,
         stmt = stmt.value(User::Phone, value).to_owned();
     }
     if let Some(value) = password {
-        let password_hash = utility::hash_password(value).or(Err(Error::WorkerCrashed))?;
+        let password_hash = utility::hash_password(value).or(Err(AuthError::Hashing))?;
         stmt = stmt.value(User::Password, password_hash).to_owned();
     }
+    // only update when stored version equal to expected version
+    if let Some(value) = version {
+        stmt = stmt.and_where(Expr::col(User::Version).eq(value)).to_owned();
+    }
 
     let (sql, values) = stmt
         .and_where(Expr::col(User::UserId).eq(id))
         .build_sqlx(PostgresQueryBuilder);
 
-    sqlx::query_with(&sql, values)
-        .execute(pool)
+    let result = sqlx::query_with(&sql, values)
+        .execute(&mut *conn)
+        .await?;
+    if result.rows_affected() == 0 && version.is_none() {
+        return Err(AuthError::not_found("user"));
+    }
+
+    Ok(result.rows_affected())
+}
+
+pub async fn update_user_state<'c, A>(conn: A, 
+    id: Uuid, 
+    disabled: Option<bool>, 
+    deleted: Option<bool>
+) -> Result<(), AuthError> 
+where A: Acquire<'c, Database = Postgres>
+{
+    let mut conn = conn.acquire().await?;
+    let mut stmt = Query::update()
+        .table(User::Table)
+        .value(User::UpdatedAt, Expr::current_timestamp())
+        .value(User::Version, Expr::col(User::Version).add(1))
+        .to_owned();
+
+    if let Some(value) = disabled {
+        stmt = stmt.value(User::Disabled, value).to_owned();
+    }
+    if let Some(value) = deleted {
+        if value {
+            stmt = stmt.value(User::DeletedAt, Expr::current_timestamp()).to_owned();
+        } else {
+            stmt = stmt.value(User::DeletedAt, Option::<DateTime<Utc>>::None).to_owned();
+        }
+    }
+
+    let (sql, values) = stmt
+        .and_where(Expr::col(User::UserId).eq(id))
+        .build_sqlx(PostgresQueryBuilder);
+
+    let result = sqlx::query_with(&sql, values)
+        .execute(&mut *conn)
         .await?;
+    if result.rows_affected() == 0 {
+        return Err(AuthError::not_found("user"));
+    }
 
     Ok(())
 }
 
-pub(crate) async fn delete_user(pool: &Pool<Postgres>, 
+pub async fn delete_user<'c, A>(conn: A, 
     id: Uuid
-) -> Result<(), Error> 
+) -> Result<u64, AuthError> 
+where A: Acquire<'c, Database = Postgres>
 {
+    let mut tx = conn.begin().await?;
+
+    // delete user tokens explicitly to count revoked sessions
+    let (sql, values) = Query::delete()
+        .from_table(Token::Table)
+        .and_where(Expr::col(Token::UserId).eq(id))
+        .build_sqlx(PostgresQueryBuilder);
+
+    let revoked = sqlx::query_with(&sql, values)
+        .execute(&mut *tx)
+        .await?
+        .rows_affected();
+
     let (sql, values) = Query::delete()
         .from_table(User::Table)
         .and_where(Expr::col(User::UserId).eq(id))
         .build_sqlx(PostgresQueryBuilder);
 
-    sqlx::query_with(&sql, values)
-        .execute(pool)
+    let result = sqlx::query_with(&sql, values)
+        .execute(&mut *tx)
         .await?;
+    if result.rows_affected() == 0 {
+        return Err(AuthError::not_found("user"));
+    }
 
-    Ok(())
+    tx.commit().await?;
+
+    Ok(revoked)
 }
 
-pub(crate) async fn add_user_role(pool: &Pool<Postgres>, 
+pub async fn add_user_role<'c, A>(conn: A, 
     id: Uuid,
     role_id: Uuid
-) -> Result<(), Error> 
+) -> Result<(), AuthError> 
+where A: Acquire<'c, Database = Postgres>
 {
+    let mut conn = conn.acquire().await?;
     let (sql, values) = Query::insert()
         .into_table(UserRole::Table)
         .columns([
@@ -230,17 +657,21 @@ pub(crate) async fn add_user_role(pool: &Pool<Postgres>,
         .build_sqlx(PostgresQueryBuilder);
 
     sqlx::query_with(&sql, values)
-        .execute(pool)
+        .execute(&mut *conn)
         .await?;
 
+    touch_user(&mut conn, id).await?;
+
     Ok(())
 }
 
-pub(crate) async fn remove_user_role(pool: &Pool<Postgres>, 
+pub async fn remove_user_role<'c, A>(conn: A, 
     id: Uuid,
     role_id: Uuid
-) -> Result<(), Error> 
+) -> Result<(), AuthError> 
+where A: Acquire<'c, Database = Postgres>
 {
+    let mut conn = conn.acquire().await?;
     let (sql, values) = Query::delete()
         .from_table(UserRole::Table)
         .and_where(Expr::col(UserRole::UserId).eq(id))
@@ -248,8 +679,105 @@ pub(crate) async fn remove_user_role(pool: &Pool<Postgres>,
         .build_sqlx(PostgresQueryBuilder);
 
     sqlx::query_with(&sql, values)
-        .execute(pool)
+        .execute(&mut *conn)
         .await?;
 
+    touch_user(&mut conn, id).await?;
+
+    Ok(())
+}
+
+// update user updated_at timestamp after its direct or group derived roles changed
+// version is kept because it guards only the user own columns against concurrent update
+pub(crate) async fn touch_user(conn: &mut PgConnection, 
+    id: Uuid
+) -> Result<(), AuthError> 
+{
+    let (sql, values) = Query::update()
+        .table(User::Table)
+        .value(User::UpdatedAt, Expr::current_timestamp())
+        .and_where(Expr::col(User::UserId).eq(id))
+        .build_sqlx(PostgresQueryBuilder);
+
+    sqlx::query_with(&sql, values)
+        .execute(&mut *conn)
+        .await?;
+
+    Ok(())
+}
+
+// email and phone values shared by more than one user, compared with the same normalization as filter
+pub async fn select_duplicate_contact<'c, A>(conn: A
+) -> Result<Vec<DuplicateContactSchema>, AuthError> 
+where A: Acquire<'c, Database = Postgres>
+{
+    let mut conn = conn.acquire().await?;
+    let duplicate = |field: &str, value: &str| Query::select()
+        .expr(Expr::val(field))
+        .expr(Expr::cust(value))
+        .expr(Expr::cust(r#"array_agg("user_id" ORDER BY "user_id")"#))
+        .from(User::Table)
+        .and_where(Expr::cust(format!("{} <> ''", value)))
+        .add_group_by([Expr::cust(value)])
+        .and_having(Expr::cust("count(*) > 1"))
+        .to_owned();
+
+    let (sql, values) = duplicate("email", r#"lower(trim("email"))"#)
+        .union(UnionType::All, duplicate("phone", r#"regexp_replace("phone", '[^0-9+]', '', 'g')"#))
+        .order_by_expr(Expr::cust("1"), Order::Asc)
+        .order_by_expr(Expr::cust("2"), Order::Asc)
+        .build_sqlx(PostgresQueryBuilder);
+
+    let rows = sqlx::query_with(&sql, values)
+        .map(|row: PgRow| {
+            DuplicateContactSchema {
+                field: row.get(0),
+                value: row.get(1),
+                user_ids: row.get(2)
+            }
+        })
+        .fetch_all(&mut *conn)
+        .await?;
+
+    Ok(rows)
+}
+
+// create unique index on normalized email and phone, fail with duplicate error when existing users share a contact
+pub async fn create_unique_contact<'c, A>(conn: A
+) -> Result<(), AuthError> 
+where A: Acquire<'c, Database = Postgres>
+{
+    let mut tx = conn.begin().await?;
+
+    if let Some(duplicate) = select_duplicate_contact(&mut *tx).await?.into_iter().next() {
+        return Err(AuthError::Duplicate { field: duplicate.field });
+    }
+    sqlx::query(r#"CREATE UNIQUE INDEX IF NOT EXISTS "user_email_key" ON "user" (lower(trim("email"))) WHERE trim("email") <> ''"#)
+        .execute(&mut *tx)
+        .await?;
+    sqlx::query(r#"CREATE UNIQUE INDEX IF NOT EXISTS "user_phone_key" ON "user" (regexp_replace("phone", '[^0-9+]', '', 'g')) WHERE regexp_replace("phone", '[^0-9+]', '', 'g') <> ''"#)
+        .execute(&mut *tx)
+        .await?;
+
+    tx.commit().await?;
+
+    Ok(())
+}
+
+pub async fn drop_unique_contact<'c, A>(conn: A
+) -> Result<(), AuthError> 
+where A: Acquire<'c, Database = Postgres>
+{
+    let mut tx = conn.begin().await?;
+
+    sqlx::query(r#"DROP INDEX IF EXISTS "user_email_key""#)
+        .execute(&mut *tx)
+        .await?;
+    sqlx::query(r#"DROP INDEX IF EXISTS "user_phone_key""#)
+        .execute(&mut *tx)
+        .await?;
+
+    tx.commit().await?;
+
     Ok(())
 }
@@ -1,6 +1,8 @@
 use sea_query::Iden;
+use sqlx::types::chrono::{DateTime, Utc};
 use uuid::Uuid;
 use rmcs_auth_api::api;
+use crate::schema::secret::Secret;
 
 #[derive(Iden)]
 pub(crate) enum Api {
@@ -11,7 +13,12 @@ pub(crate) enum Api {
     Category,
     Description,
     Password,
-    AccessKey
+    AccessKey,
+    Disabled,
+    DeletedAt,
+    CreatedAt,
+    UpdatedAt,
+    Version
 }
 
 #[derive(Iden)]
@@ -20,7 +27,10 @@ pub(crate) enum ApiProcedure {
     ApiId,
     ProcedureId,
     Name,
-    Description
+    Description,
+    CreatedAt,
+    UpdatedAt,
+    Version
 }
 
 #[derive(Debug, Default, PartialEq, Clone)]
@@ -30,9 +40,14 @@ pub struct ApiSchema {
     pub address: String,
     pub category: String,
     pub description: String,
-    pub password: String,
-    pub access_key: Vec<u8>,
+    pub password: Secret<String>,
+    pub access_key: Secret<Vec<u8>>,
     pub procedures: Vec<ProcedureSchema>,
+    pub disabled: bool,
+    pub deleted_at: Option<DateTime<Utc>>,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+    pub version: i32
 }
 
 #[derive(Debug, Default, PartialEq, Clone)]
@@ -41,7 +56,10 @@ pub struct ProcedureSchema {
     pub api_id: Uuid,
     pub name: String,
     pub description: String,
-    pub roles: Vec<String>
+    pub roles: Vec<String>,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+    pub version: i32
 }
 
 impl From<api::ApiSchema> for ApiSchema {
@@ -52,9 +70,14 @@ impl From<api::ApiSchema> for ApiSchema {
             address: value.address,
             category: value.category,
             description: value.description,
-            password: value.password,
-            access_key: value.access_key,
-            procedures: value.procedures.into_iter().map(|e| e.into()).collect()
+            password: Secret::new(value.password),
+            access_key: Secret::new(value.access_key),
+            procedures: value.procedures.into_iter().map(|e| e.into()).collect(),
+            disabled: false,
+            deleted_at: None,
+            created_at: DateTime::default(),
+            updated_at: DateTime::default(),
+            version: 0
         }
     }
 }
@@ -67,8 +90,8 @@ impl Into<api::ApiSchema> for ApiSchema {
             address: self.address,
             category: self.category,
             description: self.description,
-            password: self.password,
-            access_key: self.access_key,
+            password: self.password.into_inner(),
+            access_key: self.access_key.into_inner(),
             procedures: self.procedures.into_iter().map(|e| e.into()).collect()
         }
     }
@@ -81,7 +104,10 @@ impl From<api::ProcedureSchema> for ProcedureSchema {
             api_id: Uuid::from_slice(&value.api_id).unwrap_or_default(),
             name: value.name,
             description: value.description,
-            roles: value.roles.into_iter().map(|e| e.into()).collect()
+            roles: value.roles.into_iter().map(|e| e.into()).collect(),
+            created_at: DateTime::default(),
+            updated_at: DateTime::default(),
+            version: 0
         }
     }
 }
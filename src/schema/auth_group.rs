@@ -0,0 +1,34 @@
+use sea_query::Iden;
+use uuid::Uuid;
+
+#[derive(Iden)]
+#[allow(clippy::enum_variant_names)]
+pub(crate) enum Group {
+    Table,
+    GroupId,
+    Name,
+    Description
+}
+
+#[derive(Iden)]
+pub(crate) enum GroupUser {
+    Table,
+    GroupId,
+    UserId
+}
+
+#[derive(Iden)]
+pub(crate) enum GroupRole {
+    Table,
+    GroupId,
+    RoleId
+}
+
+#[derive(Debug, Default, PartialEq, Clone)]
+pub struct GroupSchema {
+    pub id: Uuid,
+    pub name: String,
+    pub description: String,
+    pub users: Vec<Uuid>,
+    pub roles: Vec<Uuid>
+}
@@ -1,6 +1,9 @@
 use sea_query::Iden;
+use sqlx::types::chrono::{DateTime, Utc};
 use uuid::Uuid;
 use rmcs_auth_api::role;
+use crate::schema::secret::Secret;
+use rmcs_resource_db::schema::value::DataValue;
 
 #[derive(Iden)]
 pub(crate) enum Role {
@@ -11,14 +14,33 @@ pub(crate) enum Role {
     Multi,
     IpLock,
     AccessDuration,
-    RefreshDuration
+    RefreshDuration,
+    Disabled,
+    DeletedAt,
+    CreatedAt,
+    UpdatedAt,
+    Version
 }
 
 #[derive(Iden)]
 pub(crate) enum RoleAccess {
     Table,
     RoleId,
-    ProcedureId
+    ProcedureId,
+    Deny
+}
+
+#[derive(Iden)]
+pub(crate) enum RoleAccessCondition {
+    Table,
+    Id,
+    RoleId,
+    ProcedureId,
+    Profile,
+    Operator,
+    Param,
+    Value,
+    Type
 }
 
 #[derive(Debug, Default, PartialEq, Clone)]
@@ -30,8 +52,85 @@ pub struct RoleSchema {
     pub ip_lock: bool,
     pub access_duration: i32,
     pub refresh_duration: i32,
-    pub access_key: Vec<u8>,
-    pub procedures: Vec<Uuid>
+    pub access_key: Secret<Vec<u8>>,
+    pub procedures: Vec<Uuid>,
+    pub denied_procedures: Vec<Uuid>,
+    pub disabled: bool,
+    pub deleted_at: Option<DateTime<Utc>>,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+    pub version: i32
+}
+
+#[derive(Debug, Default, PartialEq, Clone)]
+pub enum ConditionOperator {
+    #[default]
+    Equal,
+    In,
+    Greater,
+    GreaterEqual,
+    Less,
+    LessEqual
+}
+
+#[derive(Debug, Default, PartialEq, Clone)]
+pub struct AccessConditionSchema {
+    pub id: i32,
+    pub role_id: Uuid,
+    pub procedure_id: Uuid,
+    pub profile: String,
+    pub operator: ConditionOperator,
+    pub values: Vec<DataValue>,
+    pub param: Option<String>
+}
+
+impl AccessConditionSchema {
+    // check the condition against user profile values with the condition profile name
+    // compared operand is the request parameter with the condition param name if set or the condition values otherwise
+    pub fn evaluate(&self, profiles: &[(String, DataValue)], params: &[(&str, DataValue)]) -> bool
+    {
+        let operands: Vec<&DataValue> = match &self.param {
+            Some(param) => params.iter().filter(|(name, _)| name == param).map(|(_, value)| value).collect(),
+            None => self.values.iter().collect()
+        };
+        profiles.iter()
+            .filter(|(name, _)| *name == self.profile)
+            .any(|(_, value)| {
+                match self.operator {
+                    ConditionOperator::Equal => operands.first().is_some_and(|operand| value == *operand),
+                    ConditionOperator::In => operands.contains(&value),
+                    _ => {
+                        let ordering = operands.first()
+                            .and_then(|operand| Some((numeric_value(value)?, numeric_value(operand)?)))
+                            .and_then(|(value, operand)| value.partial_cmp(&operand));
+                        match (&self.operator, ordering) {
+                            (ConditionOperator::Greater, Some(order)) => order.is_gt(),
+                            (ConditionOperator::GreaterEqual, Some(order)) => order.is_ge(),
+                            (ConditionOperator::Less, Some(order)) => order.is_lt(),
+                            (ConditionOperator::LessEqual, Some(order)) => order.is_le(),
+                            _ => false
+                        }
+                    }
+                }
+            })
+    }
+}
+
+fn numeric_value(value: &DataValue) -> Option<f64>
+{
+    match value {
+        DataValue::I8(v) => Some(*v as f64),
+        DataValue::I16(v) => Some(*v as f64),
+        DataValue::I32(v) => Some(*v as f64),
+        DataValue::I64(v) => Some(*v as f64),
+        DataValue::U8(v) => Some(*v as f64),
+        DataValue::U16(v) => Some(*v as f64),
+        DataValue::U32(v) => Some(*v as f64),
+        DataValue::U64(v) => Some(*v as f64),
+        DataValue::F32(v) => Some(*v as f64),
+        DataValue::F64(v) => Some(*v),
+        _ => None
+    }
 }
 
 impl From<role::RoleSchema> for RoleSchema {
@@ -44,8 +143,14 @@ impl From<role::RoleSchema> for RoleSchema {
             ip_lock: value.ip_lock,
             access_duration: value.access_duration,
             refresh_duration: value.refresh_duration,
-            access_key: value.access_key,
-            procedures: value.procedures.into_iter().map(|u| Uuid::from_slice(&u).unwrap_or_default()).collect()
+            access_key: Secret::new(value.access_key),
+            procedures: value.procedures.into_iter().map(|u| Uuid::from_slice(&u).unwrap_or_default()).collect(),
+            denied_procedures: Vec::new(),
+            disabled: false,
+            deleted_at: None,
+            created_at: DateTime::default(),
+            updated_at: DateTime::default(),
+            version: 0
         }
     }
 }
@@ -60,8 +165,34 @@ impl Into<role::RoleSchema> for RoleSchema {
             ip_lock: self.ip_lock,
             access_duration: self.access_duration,
             refresh_duration: self.refresh_duration,
-            access_key: self.access_key,
+            access_key: self.access_key.into_inner(),
             procedures: self.procedures.into_iter().map(|u| u.as_bytes().to_vec()).collect()
         }
     }
 }
+
+impl From<i16> for ConditionOperator {
+    fn from(value: i16) -> Self {
+        match value {
+            1 => Self::In,
+            2 => Self::Greater,
+            3 => Self::GreaterEqual,
+            4 => Self::Less,
+            5 => Self::LessEqual,
+            _ => Self::Equal
+        }
+    }
+}
+
+impl From<ConditionOperator> for i16 {
+    fn from(value: ConditionOperator) -> Self {
+        match &value {
+            ConditionOperator::Equal => 0,
+            ConditionOperator::In => 1,
+            ConditionOperator::Greater => 2,
+            ConditionOperator::GreaterEqual => 3,
+            ConditionOperator::Less => 4,
+            ConditionOperator::LessEqual => 5
+        }
+    }
+}
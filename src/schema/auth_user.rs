@@ -1,6 +1,9 @@
 use sea_query::Iden;
+use sqlx::types::chrono::{DateTime, Utc};
 use uuid::Uuid;
 use rmcs_auth_api::user;
+use crate::schema::secret::Secret;
+use crate::schema::profile::UserProfileSchema;
 
 #[derive(Iden)]
 pub(crate) enum User {
@@ -9,7 +12,12 @@ pub(crate) enum User {
     Name,
     Password,
     Email,
-    Phone
+    Phone,
+    Disabled,
+    DeletedAt,
+    CreatedAt,
+    UpdatedAt,
+    Version
 }
 
 #[derive(Iden)]
@@ -25,8 +33,14 @@ pub struct UserSchema {
     pub name: String,
     pub email: String,
     pub phone: String,
-    pub password: String,
-    pub roles: Vec<UserRoleSchema>
+    pub password: Secret<String>,
+    pub roles: Vec<UserRoleSchema>,
+    pub profiles: Vec<UserProfileSchema>,
+    pub disabled: bool,
+    pub deleted_at: Option<DateTime<Utc>>,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+    pub version: i32
 }
 
 #[derive(Debug, Default, PartialEq, Clone)]
@@ -37,7 +51,16 @@ pub struct UserRoleSchema {
     pub ip_lock: bool,
     pub access_duration: i32,
     pub refresh_duration: i32,
-    pub access_key: Vec<u8>
+    pub access_key: Secret<Vec<u8>>,
+    pub group_id: Option<Uuid>
+}
+
+// users sharing the same normalized email or phone, field is either email or phone
+#[derive(Debug, Default, PartialEq, Clone)]
+pub struct DuplicateContactSchema {
+    pub field: String,
+    pub value: String,
+    pub user_ids: Vec<Uuid>
 }
 
 impl From<user::UserSchema> for UserSchema {
@@ -47,8 +70,14 @@ impl From<user::UserSchema> for UserSchema {
             name: value.name,
             email: value.email,
             phone: value.phone,
-            password: value.password,
-            roles: value.roles.into_iter().map(|e| e.into()).collect()
+            password: Secret::new(value.password),
+            roles: value.roles.into_iter().map(|e| e.into()).collect(),
+            profiles: Vec::new(),
+            disabled: false,
+            deleted_at: None,
+            created_at: DateTime::default(),
+            updated_at: DateTime::default(),
+            version: 0
         }
     }
 }
@@ -60,7 +89,7 @@ impl Into<user::UserSchema> for UserSchema {
             name: self.name,
             email: self.email,
             phone: self.phone,
-            password: self.password,
+            password: self.password.into_inner(),
             roles: self.roles.into_iter().map(|e| e.into()).collect()
         }
     }
@@ -75,7 +104,8 @@ impl From<user::UserRoleSchema> for UserRoleSchema {
             ip_lock: value.ip_lock,
             access_duration: value.access_duration,
             refresh_duration: value.refresh_duration,
-            access_key: value.access_key
+            access_key: Secret::new(value.access_key),
+            group_id: None
         }
     }
 }
@@ -89,7 +119,7 @@ impl Into<user::UserRoleSchema> for UserRoleSchema {
             ip_lock: self.ip_lock,
             access_duration: self.access_duration,
             refresh_duration: self.refresh_duration,
-            access_key: self.access_key
+            access_key: self.access_key.into_inner()
         }
     }
 }
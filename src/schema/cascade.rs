@@ -0,0 +1,16 @@
+#[derive(Debug, Default, PartialEq, Clone)]
+pub struct DeleteSummary {
+    pub apis: u64,
+    pub procedures: u64,
+    pub roles: u64,
+    pub role_access: u64,
+    pub access_conditions: u64,
+    pub role_profiles: u64,
+    pub users: u64,
+    pub user_roles: u64,
+    pub user_profiles: u64,
+    pub tokens: u64,
+    pub groups: u64,
+    pub group_users: u64,
+    pub group_roles: u64
+}
@@ -0,0 +1,56 @@
+use uuid::Uuid;
+
+// channel of notification emitted by change triggers of auth tables
+pub(crate) const CHANGE_CHANNEL: &str = "auth_change";
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum ChangeKind {
+    Insert,
+    Update,
+    Delete
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub enum ChangeEvent {
+    Api { id: Uuid, kind: ChangeKind },
+    Procedure { id: Uuid, api_id: Uuid, kind: ChangeKind },
+    Role { id: Uuid, api_id: Uuid, kind: ChangeKind },
+    RoleAccess { role_id: Uuid, procedure_id: Uuid, kind: ChangeKind },
+    UserRole { user_id: Uuid, role_id: Uuid, kind: ChangeKind },
+    GroupUser { group_id: Uuid, user_id: Uuid, kind: ChangeKind },
+    GroupRole { group_id: Uuid, role_id: Uuid, kind: ChangeKind },
+    Token { access_id: i32, user_id: Uuid, kind: ChangeKind },
+    // connection to database was lost so changes may be missed and cached data should be reloaded
+    Resync
+}
+
+impl ChangeEvent {
+
+    // payload is table name, trigger operation, row key and parent key separated by colon
+    pub(crate) fn from_payload(payload: &str) -> Option<Self>
+    {
+        let mut parts = payload.split(':');
+        let table = parts.next()?;
+        let kind = match parts.next()? {
+            "INSERT" => ChangeKind::Insert,
+            "UPDATE" => ChangeKind::Update,
+            "DELETE" => ChangeKind::Delete,
+            _ => return None
+        };
+        let key = parts.next()?;
+        let parent = parts.next();
+        let uuid = |value: Option<&str>| value.and_then(|v| Uuid::parse_str(v).ok());
+        match table {
+            "api" => Some(ChangeEvent::Api { id: uuid(Some(key))?, kind }),
+            "api_procedure" => Some(ChangeEvent::Procedure { id: uuid(Some(key))?, api_id: uuid(parent)?, kind }),
+            "role" => Some(ChangeEvent::Role { id: uuid(Some(key))?, api_id: uuid(parent)?, kind }),
+            "role_access" => Some(ChangeEvent::RoleAccess { role_id: uuid(Some(key))?, procedure_id: uuid(parent)?, kind }),
+            "user_role" => Some(ChangeEvent::UserRole { user_id: uuid(Some(key))?, role_id: uuid(parent)?, kind }),
+            "group_user" => Some(ChangeEvent::GroupUser { group_id: uuid(Some(key))?, user_id: uuid(parent)?, kind }),
+            "group_role" => Some(ChangeEvent::GroupRole { group_id: uuid(Some(key))?, role_id: uuid(parent)?, kind }),
+            "token" => Some(ChangeEvent::Token { access_id: key.parse().ok()?, user_id: uuid(parent)?, kind }),
+            _ => None
+        }
+    }
+
+}
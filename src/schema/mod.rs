@@ -2,4 +2,10 @@ pub mod api;
 pub mod auth_role;
 pub mod auth_user;
 pub mod auth_token;
+pub mod auth_group;
 pub mod profile;
+pub mod permission;
+pub mod cascade;
+pub mod page;
+pub mod secret;
+pub mod change;
@@ -0,0 +1,73 @@
+use uuid::Uuid;
+use crate::error::AuthError;
+
+#[derive(Debug, Default, PartialEq, Clone)]
+pub struct Page<T> {
+    pub items: Vec<T>,
+    pub next_cursor: Option<String>
+}
+
+#[derive(Debug, Default, PartialEq, Clone, Copy)]
+pub enum PageOrder {
+    #[default]
+    Id,
+    Name
+}
+
+// keyset position used to fetch rows after the last row of previous page
+#[derive(Debug, Clone)]
+pub(crate) struct Keyset {
+    pub(crate) order: PageOrder,
+    pub(crate) size: u32,
+    pub(crate) after: Option<(String, Uuid)>
+}
+
+impl Keyset {
+    pub(crate) fn new(order: PageOrder, size: u32, cursor: Option<&str>) -> Result<Self, AuthError> {
+        let after = match cursor {
+            Some(cursor) => Some(decode_cursor(cursor)?),
+            None => None
+        };
+        Ok(Keyset { order, size, after })
+    }
+}
+
+impl<T> Page<T> {
+    // rows are fetched with one extra parent row to detect whether next page exists
+    pub(crate) fn new(mut items: Vec<T>, size: u32, key: impl Fn(&T) -> (&str, Uuid)) -> Self {
+        let next_cursor = if items.len() > size as usize {
+            items.truncate(size as usize);
+            items.last().map(|item| {
+                let (name, id) = key(item);
+                encode_cursor(name, id)
+            })
+        } else {
+            None
+        };
+        Page { items, next_cursor }
+    }
+}
+
+// cursor is hex encoded bytes of last row id followed by last row name
+fn encode_cursor(name: &str, id: Uuid) -> String
+{
+    id.as_bytes().iter()
+        .chain(name.as_bytes())
+        .map(|b| format!("{:02x}", b))
+        .collect()
+}
+
+fn decode_cursor(cursor: &str) -> Result<(String, Uuid), AuthError>
+{
+    let invalid = || AuthError::Validation(String::from("invalid page cursor"));
+    if !cursor.len().is_multiple_of(2) || cursor.len() < 32 {
+        return Err(invalid());
+    }
+    let bytes = (0..cursor.len()).step_by(2)
+        .map(|i| cursor.get(i..i + 2).and_then(|b| u8::from_str_radix(b, 16).ok()))
+        .collect::<Option<Vec<u8>>>()
+        .ok_or_else(invalid)?;
+    let id = Uuid::from_slice(&bytes[..16]).map_err(|_| invalid())?;
+    let name = String::from_utf8(bytes[16..].to_vec()).map_err(|_| invalid())?;
+    Ok((name, id))
+}
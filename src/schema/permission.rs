@@ -0,0 +1,79 @@
+use uuid::Uuid;
+
+#[derive(Debug, Default, PartialEq, Clone)]
+pub struct PermissionSchema {
+    pub user_id: Uuid,
+    pub user_name: String,
+    pub role_id: Uuid,
+    pub role_name: String,
+    pub procedure_id: Uuid,
+    pub procedure_name: String,
+    pub conditional: bool
+}
+
+#[derive(Debug, Default, PartialEq, Clone)]
+pub struct PermissionMatrix {
+    pub api_id: Uuid,
+    pub permissions: Vec<PermissionSchema>
+}
+
+impl PermissionMatrix {
+
+    pub fn to_csv(&self) -> String
+    {
+        let mut csv = String::from("user_id,user_name,role_id,role_name,procedure_id,procedure_name,conditional\n");
+        for p in &self.permissions {
+            let fields = [
+                p.user_id.to_string(),
+                csv_field(&p.user_name),
+                p.role_id.to_string(),
+                csv_field(&p.role_name),
+                p.procedure_id.to_string(),
+                csv_field(&p.procedure_name),
+                p.conditional.to_string()
+            ];
+            csv.push_str(&fields.join(","));
+            csv.push('\n');
+        }
+        csv
+    }
+
+    pub fn to_json(&self) -> String
+    {
+        let permissions: Vec<String> = self.permissions.iter().map(|p| {
+            format!("{{\"user_id\":\"{}\",\"user_name\":{},\"role_id\":\"{}\",\"role_name\":{},\"procedure_id\":\"{}\",\"procedure_name\":{},\"conditional\":{}}}",
+                p.user_id, json_string(&p.user_name), p.role_id, json_string(&p.role_name), p.procedure_id, json_string(&p.procedure_name), p.conditional
+            )
+        })
+        .collect();
+        format!("{{\"api_id\":\"{}\",\"permissions\":[{}]}}", self.api_id, permissions.join(","))
+    }
+
+}
+
+fn csv_field(value: &str) -> String
+{
+    if value.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_owned()
+    }
+}
+
+fn json_string(value: &str) -> String
+{
+    let mut json = String::from("\"");
+    for c in value.chars() {
+        match c {
+            '"' => json.push_str("\\\""),
+            '\\' => json.push_str("\\\\"),
+            '\n' => json.push_str("\\n"),
+            '\r' => json.push_str("\\r"),
+            '\t' => json.push_str("\\t"),
+            c if (c as u32) < 0x20 => json.push_str(&format!("\\u{:04x}", c as u32)),
+            c => json.push(c)
+        }
+    }
+    json.push('"');
+    json
+}
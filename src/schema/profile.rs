@@ -1,4 +1,5 @@
 use sea_query::Iden;
+use sqlx::types::chrono::{DateTime, Utc};
 use uuid::Uuid;
 use rmcs_auth_api::profile;
 use rmcs_resource_db::schema::value::{DataValue, DataType};
@@ -10,7 +11,9 @@ pub(crate) enum ProfileRole {
     RoleId,
     Name,
     Type,
-    Mode
+    Mode,
+    CreatedAt,
+    UpdatedAt
 }
 
 #[derive(Iden)]
@@ -21,7 +24,9 @@ pub(crate) enum ProfileUser {
     Name,
     Order,
     Value,
-    Type
+    Type,
+    CreatedAt,
+    UpdatedAt
 }
 
 #[derive(Debug, Default, PartialEq, Clone)]
@@ -39,7 +44,9 @@ pub struct RoleProfileSchema {
     pub role_id: Uuid,
     pub name: String,
     pub value_type: DataType,
-    pub mode: ProfileMode
+    pub mode: ProfileMode,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>
 }
 
 #[derive(Debug, Default, PartialEq, Clone)]
@@ -48,7 +55,9 @@ pub struct UserProfileSchema {
     pub user_id: Uuid,
     pub name: String,
     pub value: DataValue,
-    pub order: i16
+    pub order: i16,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>
 }
 
 impl From<profile::RoleProfileSchema> for RoleProfileSchema {
@@ -58,7 +67,9 @@ impl From<profile::RoleProfileSchema> for RoleProfileSchema {
             role_id: Uuid::from_slice(&value.role_id).unwrap_or_default(),
             name: value.name,
             value_type: DataType::from(value.value_type),
-            mode: ProfileMode::from(value.mode)
+            mode: ProfileMode::from(value.mode),
+            created_at: DateTime::default(),
+            updated_at: DateTime::default()
         }
     }
 }
@@ -82,7 +93,9 @@ impl From<profile::UserProfileSchema> for UserProfileSchema {
             user_id: Uuid::from_slice(&value.user_id).unwrap_or_default(),
             name: value.name,
             order: value.order as i16,
-            value: DataValue::from_bytes(value.value_bytes.as_slice(), DataType::from(value.value_type))
+            value: DataValue::from_bytes(value.value_bytes.as_slice(), DataType::from(value.value_type)),
+            created_at: DateTime::default(),
+            updated_at: DateTime::default()
         }
     }
 }
@@ -0,0 +1,68 @@
+use std::future::Future;
+use std::ops::DerefMut;
+use std::sync::Arc;
+use sqlx::{Pool, Transaction};
+use sqlx::pool::PoolConnection;
+use sqlx::postgres::{Postgres, PgConnection};
+use futures::lock::{Mutex, MutexGuard, MappedMutexGuard};
+
+use crate::error::AuthError;
+use crate::cache::AuthCache;
+use crate::{Auth, AuthOptions};
+
+// source of database connection used by every Auth method
+// a pool hands out a new connection per call while a transaction hands out its own connection
+pub trait AuthExecutor: Send + Sync {
+    type Conn<'a>: DerefMut<Target = PgConnection> + Send where Self: 'a;
+
+    fn conn(&self) -> impl Future<Output = Result<Self::Conn<'_>, AuthError>> + Send;
+}
+
+impl AuthExecutor for Pool<Postgres> {
+    type Conn<'a> = PoolConnection<Postgres>;
+
+    async fn conn(&self) -> Result<Self::Conn<'_>, AuthError> {
+        Ok(self.acquire().await?)
+    }
+}
+
+pub struct TxConn {
+    tx: Mutex<Transaction<'static, Postgres>>,
+    cache: Option<Arc<AuthCache>>
+}
+
+impl AuthExecutor for TxConn {
+    type Conn<'a> = MappedMutexGuard<'a, Transaction<'static, Postgres>, PgConnection>;
+
+    // calls on the same transaction are serialized, the connection is released at the end of each call
+    // or when a stream is dropped so other calls wait until a stream of the transaction is dropped
+    async fn conn(&self) -> Result<Self::Conn<'_>, AuthError> {
+        Ok(MutexGuard::map(self.tx.lock().await, |tx| &mut **tx))
+    }
+}
+
+// Auth running all methods inside one database transaction, reads bypass cache
+// and writes are visible to other Auth only after commit
+pub type AuthTx = Auth<TxConn>;
+
+impl AuthTx {
+
+    pub(crate) fn new(tx: Transaction<'static, Postgres>, options: AuthOptions, cache: Option<Arc<AuthCache>>) -> AuthTx {
+        Auth::with_executor(TxConn { tx: Mutex::new(tx), cache }, options)
+    }
+
+    // writes inside transaction are not tracked so whole cache is cleared after commit
+    pub async fn commit(self) -> Result<(), AuthError> {
+        let TxConn { tx, cache } = self.pool;
+        tx.into_inner().commit().await?;
+        if let Some(cache) = &cache {
+            cache.clear();
+        }
+        Ok(())
+    }
+
+    pub async fn rollback(self) -> Result<(), AuthError> {
+        Ok(self.pool.tx.into_inner().rollback().await?)
+    }
+
+}
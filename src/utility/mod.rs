@@ -10,6 +10,24 @@ pub(crate) fn hash_password(password: &str) -> Result<String, argon2::password_h
     Ok(password_hash.to_string())
 }
 
+// escape LIKE wildcard characters of user input with backslash which is postgres default LIKE escape character
+// and append wildcard for prefix or contains search
+pub(crate) fn like_pattern(value: &str, prefix: bool) -> String
+{
+    let mut pattern = String::with_capacity(value.len() + 2);
+    if !prefix {
+        pattern.push('%');
+    }
+    for c in value.chars() {
+        if matches!(c, '%' | '_' | '\\') {
+            pattern.push('\\');
+        }
+        pattern.push(c);
+    }
+    pattern.push('%');
+    pattern
+}
+
 pub fn generate_access_key() -> Vec<u8>
 {
     (0..32).map(|_| thread_rng().gen_range(0..255)).collect()
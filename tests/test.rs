@@ -3,10 +3,13 @@ mod tests {
     use sqlx::{Pool, Error};
     use sqlx::postgres::{Postgres, PgPoolOptions};
     use sqlx::types::chrono::DateTime;
+    use std::time::Duration;
     use uuid::Uuid;
+    use futures::{StreamExt, TryStreamExt};
     use argon2::{Argon2, PasswordHash, PasswordVerifier};
-    use rmcs_auth_db::Auth;
+    use rmcs_auth_db::{Auth, AuthError, AuthOptions, OrderOption, ConditionOperator, SearchOption, PageOrder, ApiFilter, RoleFilter, UserFilter, ChangeEvent, ChangeKind};
     use rmcs_auth_db::utility::generate_access_key;
+    use rmcs_auth_db::operation;
     use rmcs_auth_db::ProfileMode::*;
     use rmcs_resource_db::{DataType::*, DataValue::*};
 
@@ -22,7 +25,7 @@ mod tests {
 
     async fn truncate_tables(pool: &Pool<Postgres>) -> Result<(), Error>
     {
-        let sql = "TRUNCATE TABLE \"profile_user\", \"profile_role\", \"token\", \"user_role\", \"user\", \"role_access\", \"role\", \"api_procedure\", \"api\";";
+        let sql = "TRUNCATE TABLE \"profile_user\", \"profile_role\", \"token\", \"group_role\", \"group_user\", \"group\", \"user_role\", \"user\", \"role_access_condition\", \"role_access\", \"role\", \"api_procedure\", \"api\";";
         sqlx::query(sql)
             .execute(pool)
             .await?;
@@ -35,7 +38,7 @@ mod tests {
         unsafe { std::env::set_var("RUST_BACKTRACE", "1"); }
 
         let pool = get_connection_pool().await.unwrap();
-        let auth = Auth::new_with_pool(pool);
+        let mut auth = Auth::new_with_pool(pool);
 
         // truncate all auth database tables before test
         truncate_tables(&auth.pool).await.unwrap();
@@ -55,7 +58,7 @@ mod tests {
         // get newly created resource at the first of resource API list
         let apis = auth.list_api_by_category("RESOURCE").await.unwrap();
         let api_ids: Vec<Uuid> = apis.iter().map(|e| e.id).collect();
-        let api = auth.read_api(api_id1).await.unwrap();
+        let api = auth.read_api_with_secrets(api_id1).await.unwrap();
         let api_proc_ids: Vec<Uuid> = api.procedures.iter().map(|e| e.id).collect();
 
         // get newly created procedure at the first of procedure list
@@ -68,7 +71,7 @@ mod tests {
         assert!(proc_ids.contains(&proc_id1));
         assert_eq!(api_proc_ids, proc_ids);
 
-        let hash = api.password;
+        let hash = api.password.into_inner();
         let parsed_hash = PasswordHash::new(hash.as_str()).unwrap();
         assert!(Argon2::default().verify_password(password_api.as_bytes(), &parsed_hash).is_ok());
 
@@ -85,7 +88,7 @@ mod tests {
         // get role data
         let roles = auth.list_role_by_api(api_id1).await.unwrap();
         let role_ids: Vec<Uuid> = roles.iter().map(|e| e.id).collect();
-        let role = auth.read_role(role_id1).await.unwrap();
+        let role = auth.read_role_with_secrets(role_id1).await.unwrap();
 
         assert!(role_ids.contains(&role_id1));
         assert_eq!(role.name, "administrator");
@@ -95,7 +98,7 @@ mod tests {
         assert!(role.procedures.contains(&proc_id2));
         assert!(role.procedures.contains(&proc_id3));
 
-        let access_key = role.access_key;
+        let access_key = role.access_key.into_inner();
         assert_eq!(access_key.len(), 32);
 
         // update a resource API and its procedure and role
@@ -110,7 +113,7 @@ mod tests {
         // get updated resource API schema
         let api = auth.read_api_by_name(api_name).await.unwrap();
         let procedure = auth.read_procedure_by_name(api_id1, proc_name).await.unwrap();
-        let role = auth.read_role_by_name(api_id1, role_name).await.unwrap();
+        let role = auth.read_role_by_name_with_secrets(api_id1, role_name).await.unwrap();
 
         assert_eq!(api.name, api_name);
         assert_eq!(api.description, "New resource api");
@@ -118,7 +121,53 @@ mod tests {
         assert_eq!(procedure.description, "Read resource data");
         assert_eq!(role.name, role_name);
         assert_eq!(role.ip_lock, true);
-        assert_ne!(role.access_key, access_key);
+        assert_ne!(role.access_key.expose(), &access_key);
+
+        // update role with expected version and retry with stale version
+        let version = auth.update_role_versioned(role_id1, role.version, None, None, None, None, Some(28800)).await.unwrap();
+        let try_version = auth.update_role_versioned(role_id1, role.version, None, None, None, None, Some(28800)).await;
+
+        assert_eq!(version, role.version + 1);
+        assert!(matches!(try_version, Err(AuthError::Conflict { .. })));
+
+        // cached role read invalidated by own update and stale after update from uncached auth until invalidated
+        let auth_cache = auth.with_cache(Duration::from_secs(60), 100);
+        let role_cache = auth_cache.read_role(role_id2).await.unwrap();
+        auth_cache.update_role(role_id2, None, None, None, Some(1800), None).await.unwrap();
+        let role_updated = auth_cache.read_role(role_id2).await.unwrap();
+        auth.update_role(role_id2, None, None, None, Some(900), None).await.unwrap();
+        let role_stale = auth_cache.read_role(role_id2).await.unwrap();
+        auth_cache.invalidate_role(role_id2);
+        let role_fresh = auth_cache.read_role(role_id2).await.unwrap();
+
+        assert_eq!(role_cache.access_duration, 900);
+        assert_eq!(role_updated.access_duration, 1800);
+        assert_eq!(role_stale.access_duration, 1800);
+        assert_eq!(role_fresh.access_duration, 900);
+
+        // cached role expired after ttl and evicted when cache capacity exceeded
+        let auth_ttl = auth.with_cache(Duration::from_millis(200), 100);
+        let auth_size = auth.with_cache(Duration::from_secs(60), 1);
+        auth_ttl.read_role(role_id2).await.unwrap();
+        auth_size.read_role(role_id2).await.unwrap();
+        auth_size.read_role(role_id1).await.unwrap();
+        auth.update_role(role_id2, None, None, None, Some(1200), None).await.unwrap();
+        let role_size = auth_size.read_role(role_id2).await.unwrap();
+        let role_live = auth_ttl.read_role(role_id2).await.unwrap();
+        tokio::time::sleep(Duration::from_millis(300)).await;
+        let role_expired = auth_ttl.read_role(role_id2).await.unwrap();
+        auth.update_role(role_id2, None, None, None, Some(900), None).await.unwrap();
+
+        assert_eq!(role_size.access_duration, 1200);
+        assert_eq!(role_live.access_duration, 900);
+        assert_eq!(role_expired.access_duration, 1200);
+
+        // receive role change event emitted by other connection
+        let mut changes = Box::pin(auth.subscribe_changes().await.unwrap());
+        auth.update_role(role_id2, None, None, None, None, Some(604800)).await.unwrap();
+        let change = tokio::time::timeout(Duration::from_secs(5), changes.next()).await.unwrap();
+
+        assert_eq!(change, Some(ChangeEvent::Role { id: role_id2, api_id: api_id1, kind: ChangeKind::Update }));
 
         // create new user and add associated roles
         let password_admin = "Adm1n_P4s5w0rd";
@@ -133,25 +182,238 @@ mod tests {
         // get user data
         let users = auth.list_user_by_role(role_id3).await.unwrap();
         let user_ids: Vec<Uuid> = users.iter().map(|e| e.id).collect();
-        let user = auth.read_user(user_id1).await.unwrap();
+        let user = auth.read_user_with_secrets(user_id1).await.unwrap();
 
         assert!(user_ids.contains(&user_id1));
         assert_eq!(user.name, "administrator");
         assert_eq!(user.email, "admin@mail.co");
         assert_eq!(user.phone, "+6281234567890");
 
-        let hash = user.password;
+        let hash = user.password.into_inner();
         let parsed_hash = PasswordHash::new(hash.as_str()).unwrap();
         assert!(Argon2::default().verify_password(password_admin.as_bytes(), &parsed_hash).is_ok());
 
-        // update user
+        // default read omit password hash and role access key and redact them from debug output
+        let user = auth.read_user(user_id1).await.unwrap();
+
+        assert!(user.password.expose().is_empty());
+        assert!(user.roles.iter().all(|r| r.access_key.expose().is_empty()));
+        assert!(!format!("{:?}", auth.read_user_with_secrets(user_id1).await.unwrap()).contains(&hash));
+
+        // deny a procedure in another role of the same user
+        assert!(auth.check_access(user_id1, proc_id3).await.unwrap());
+        auth.add_user_role(user_id1, role_id2).await.unwrap();
+        auth.add_role_deny(role_id2, proc_id3).await.unwrap();
+        let role = auth.read_role(role_id2).await.unwrap();
+
+        assert!(role.denied_procedures.contains(&proc_id3));
+        assert!(!role.procedures.contains(&proc_id3));
+        assert!(!auth.check_access(user_id1, proc_id3).await.unwrap());
+        assert!(auth.check_access(user_id1, proc_id1).await.unwrap());
+        assert!(!auth.check_access(user_id2, proc_id2).await.unwrap());
+
+        // removing deny keep allow of the same procedure and removing allow keep deny
+        auth.remove_role_deny(role_id2, proc_id1).await.unwrap();
+        auth.remove_role_access(role_id2, proc_id3).await.unwrap();
+        let role = auth.read_role(role_id2).await.unwrap();
+
+        assert!(role.procedures.contains(&proc_id1));
+        assert!(role.denied_procedures.contains(&proc_id3));
+
+        auth.remove_role_deny(role_id2, proc_id3).await.unwrap();
+        auth.remove_user_role(user_id1, role_id2).await.unwrap();
+
+        // export effective permission of resource API
+        let matrix = auth.permission_matrix(api_id1).await.unwrap();
+
+        assert_eq!(matrix.permissions.len(), 4);
+        assert!(matrix.permissions.iter().any(|p| p.user_id == user_id2 && p.procedure_id == proc_id1));
+        assert_eq!(matrix.to_csv().lines().count(), 5);
+        assert!(matrix.to_json().contains(&proc_id3.to_string()));
+
+        // deny in another role hide the grant, conditional deny mark it conditional and disabled role deny is ignored
+        let role_id4 = auth.create_role(Uuid::new_v4(), api_id1, "blocked", false, false, 900, 28800).await.unwrap();
+        auth.add_role_deny(role_id4, proc_id1).await.unwrap();
+        auth.add_user_role(user_id2, role_id4).await.unwrap();
+        let matrix_denied = auth.permission_matrix(api_id1).await.unwrap();
+        let condition_id = auth.create_access_condition(role_id4, proc_id1, "age", ConditionOperator::GreaterEqual, &[], Some("min_age")).await.unwrap();
+        let matrix_conditional = auth.permission_matrix(api_id1).await.unwrap();
+        auth.delete_access_condition(condition_id).await.unwrap();
+        auth.disable_role(role_id4).await.unwrap();
+        let matrix_disabled = auth.permission_matrix(api_id1).await.unwrap();
+        auth.remove_user_role(user_id2, role_id4).await.unwrap();
+        auth.remove_role_deny(role_id4, proc_id1).await.unwrap();
+        auth.delete_role(role_id4).await.unwrap();
+
+        assert!(!matrix_denied.permissions.iter().any(|p| p.user_id == user_id2 && p.procedure_id == proc_id1));
+        assert!(matrix_conditional.permissions.iter().any(|p| p.user_id == user_id2 && p.procedure_id == proc_id1 && p.conditional));
+        assert!(matrix_disabled.permissions.iter().any(|p| p.user_id == user_id2 && p.procedure_id == proc_id1 && !p.conditional));
+
+        // create group and assign role to user through group membership
+        let group_id = auth.create_group(Uuid::new_v4(), "engineer", "").await.unwrap();
+        auth.add_group_role(group_id, role_id1).await.unwrap();
+        auth.add_group_role(group_id, role_id3).await.unwrap();
+        auth.add_group_user(group_id, user_id2).await.unwrap();
+        let group = auth.read_group(group_id).await.unwrap();
+        let user = auth.read_user(user_id2).await.unwrap();
+        let roles = auth.list_role_by_user(user_id2).await.unwrap();
+        let role_ids: Vec<Uuid> = roles.iter().map(|e| e.id).collect();
+
+        assert_eq!(group.users, [user_id2]);
+        assert_eq!(group.roles.len(), 2);
+        assert_eq!(user.roles.len(), 3);
+        assert!(user.roles.iter().any(|r| r.api_id == api_id1 && r.role == role_name && r.group_id == Some(group_id)));
+        assert!(user.roles.iter().any(|r| r.api_id == api_id2 && r.group_id.is_none()));
+        assert!(role_ids.contains(&role_id1));
+        assert!(auth.check_access(user_id2, proc_id3).await.unwrap());
+
+        // group changes received by listener invalidate cached users of the group
+        let auth_cache = auth.with_cache(Duration::from_secs(60), 100);
+        let mut changes = Box::pin(auth_cache.subscribe_changes().await.unwrap());
+        auth_cache.read_user(user_id2).await.unwrap();
+        auth.remove_group_user(group_id, user_id2).await.unwrap();
+        let change_user = tokio::time::timeout(Duration::from_secs(5), changes.next()).await.unwrap();
+        let user_removed = auth_cache.read_user(user_id2).await.unwrap();
+        auth.add_group_user(group_id, user_id2).await.unwrap();
+        tokio::time::timeout(Duration::from_secs(5), changes.next()).await.unwrap();
+        let user_added = auth_cache.read_user(user_id2).await.unwrap();
+        auth.remove_group_role(group_id, role_id1).await.unwrap();
+        let change_role = tokio::time::timeout(Duration::from_secs(5), changes.next()).await.unwrap();
+        let user_role = auth_cache.read_user(user_id2).await.unwrap();
+
+        assert_eq!(change_user, Some(ChangeEvent::GroupUser { group_id, user_id: user_id2, kind: ChangeKind::Delete }));
+        assert!(user_removed.roles.iter().all(|r| r.group_id.is_none()));
+        assert!(user_added.roles.iter().any(|r| r.role == role_name && r.group_id == Some(group_id)));
+        assert_eq!(change_role, Some(ChangeEvent::GroupRole { group_id, role_id: role_id1, kind: ChangeKind::Delete }));
+        assert!(user_role.roles.iter().all(|r| r.group_id.is_none()));
+
+        auth.remove_group_user(group_id, user_id2).await.unwrap();
+        auth.remove_group_role(group_id, role_id3).await.unwrap();
+        auth.delete_group(group_id).await.unwrap();
+
+        // group membership change touch user but keep version so versioned update still succeed
+        let user_touched = auth.read_user(user_id2).await.unwrap();
+        let version = auth.update_user_versioned(user_id2, user.version, None, None, None, None).await.unwrap();
+
+        assert!(user_touched.updated_at > user.updated_at);
+        assert_eq!(version, user.version + 1);
+
+        // update user after last write touching both users
         let password_new = "N3w_P4s5w0rd";
+        let users = auth.list_user_by_ids(&[user_id1, user_id2]).await.unwrap();
+        let updated_at = users.iter().map(|u| u.updated_at).max().unwrap();
         auth.update_user(user_id2, None, None, None, Some(password_new)).await.unwrap();
 
         // get updated user
-        let user = auth.read_user_by_name("username").await.unwrap();
-
-        assert_ne!(user.password, hash);
+        let user = auth.read_user_by_name_with_secrets("username").await.unwrap();
+
+        assert_ne!(user.password.expose(), &hash);
+        assert!(user.updated_at > user.created_at);
+
+        // get users changed after last update timestamp
+        let users = auth.list_user_updated_since(updated_at + Duration::from_micros(1)).await.unwrap();
+
+        assert_eq!(users.len(), 1);
+        assert_eq!(users[0].id, user_id2);
+
+        // shared phone is reported and ambiguous, so unique contact can not be enabled yet
+        let mut duplicate_ids = vec![user_id1, user_id2];
+        duplicate_ids.sort();
+        let duplicates = auth.list_duplicate_contact().await.unwrap();
+        let try_phone = auth.read_user_by_phone("+62 812-3456-7890").await;
+        let try_unique = auth.enable_unique_contact().await;
+
+        assert_eq!(duplicates.len(), 1);
+        assert_eq!(duplicates[0].field, "phone");
+        assert_eq!(duplicates[0].value, "+6281234567890");
+        assert_eq!(duplicates[0].user_ids, duplicate_ids);
+        assert!(matches!(try_phone, Err(AuthError::Duplicate { field }) if field == "phone"));
+        assert!(matches!(try_unique, Err(AuthError::Duplicate { field }) if field == "phone"));
+
+        // resolve duplicate phone then enable unique email and phone
+        auth.update_user(user_id2, None, None, Some("+6281234567891"), None).await.unwrap();
+        auth.enable_unique_contact().await.unwrap();
+
+        // read user by email, phone and login identifier
+        let user_email = auth.read_user_by_email(" User@Mail.CO ").await.unwrap();
+        let user_phone = auth.read_user_by_phone("+62 812-3456-7891").await.unwrap();
+        let user_identifier = auth.read_user_by_identifier("admin@mail.co").await.unwrap();
+        let try_email = auth.create_user(Uuid::new_v4(), "duplicate", "USER@mail.co", "", password_user).await;
+
+        assert_eq!(user_email.id, user_id2);
+        assert_eq!(user_phone.id, user_id2);
+        assert_eq!(user_identifier.id, user_id1);
+        assert!(matches!(try_email, Err(AuthError::Duplicate { field }) if field == "email"));
+        assert!(auth.list_duplicate_contact().await.unwrap().is_empty());
+
+        auth.disable_unique_contact().await.unwrap();
+
+        // search user by name with escaped wildcard and case insensitive
+        let users_contains = auth.list_user_by_name("NAME").await.unwrap();
+        let users_wildcard = auth.list_user_by_name("user_ame%").await.unwrap();
+        auth.set_search(SearchOption::Prefix);
+        let users_prefix = auth.list_user_by_name("name").await.unwrap();
+        auth.set_search(SearchOption::Contains);
+
+        assert_eq!(users_contains.len(), 1);
+        assert_eq!(users_contains[0].id, user_id2);
+        assert!(users_wildcard.is_empty());
+        assert!(users_prefix.is_empty());
+
+        // list users with limit, offset and order applied to users instead of joined roles
+        let options = AuthOptions::default().limit(1).offset(1).order(vec![OrderOption::NameAsc]);
+        let users_page = auth.with_options(options).list_user_option(None, None, None).await.unwrap();
+
+        assert_eq!(users_page.len(), 1);
+        assert_eq!(users_page[0].id, user_id2);
+        assert_eq!(users_page[0].roles.len(), 2);
+
+        // count users and roles matching the same filters of listing
+        let user_count = auth.count_user_option(None, Some(role_id3), None).await.unwrap();
+        let role_count = auth.count_role_option(Some(api_id1), None, None).await.unwrap();
+
+        assert_eq!(user_count, 2);
+        assert_eq!(role_count, 2);
+
+        // list users with combined filters where all filters must match
+        let filter = UserFilter::default().ids(&[user_id1, user_id2]).api_id(api_id1).lacks_role(role_id2);
+        let users_filter = auth.list_user(&filter).await.unwrap();
+
+        assert_eq!(users_filter.len(), 1);
+        assert_eq!(users_filter[0].id, user_id1);
+        assert_eq!(users_filter[0].roles.len(), 2);
+
+        // list users, roles and apis with children fetched in a second query identical to streams folding joined rows
+        let auth_inactive = auth.with_options(AuthOptions::default().with_inactive(true));
+        for auth in [&auth, &auth_inactive] {
+            let users_stream: Vec<_> = auth.stream_users(&UserFilter::default()).try_collect().await.unwrap();
+            let users_list = auth.list_user(&UserFilter::default()).await.unwrap();
+            let roles_stream: Vec<_> = auth.stream_roles(&RoleFilter::default()).try_collect().await.unwrap();
+            let roles_list = auth.list_role(&RoleFilter::default()).await.unwrap();
+            let apis_stream: Vec<_> = auth.stream_apis(&ApiFilter::default()).try_collect().await.unwrap();
+            let apis_list = auth.list_api(&ApiFilter::default()).await.unwrap();
+
+            assert!(users_list.iter().all(|u| !u.roles.is_empty()));
+            assert!(roles_list.iter().any(|r| r.procedures.len() > 1));
+            assert!(apis_list.iter().any(|a| a.procedures.iter().any(|p| !p.roles.is_empty())));
+            assert_eq!(users_stream, users_list);
+            assert_eq!(roles_stream, roles_list);
+            assert_eq!(apis_stream, apis_list);
+        }
+
+        // list users with keyset pagination using cursor of previous page
+        let page1 = auth.list_user_page(None, None, None, PageOrder::Name, 1, None).await.unwrap();
+        let page2 = auth.list_user_page(None, None, None, PageOrder::Name, 1, page1.next_cursor.as_deref()).await.unwrap();
+        let try_page = auth.list_user_page(None, None, None, PageOrder::Name, 1, Some("cursor")).await;
+
+        assert_eq!(page1.items.len(), 1);
+        assert_eq!(page1.items[0].id, user_id1);
+        assert!(page1.next_cursor.is_some());
+        assert_eq!(page2.items.len(), 1);
+        assert_eq!(page2.items[0].id, user_id2);
+        assert_eq!(page2.items[0].roles.len(), 2);
+        assert!(page2.next_cursor.is_none());
+        assert!(matches!(try_page, Err(AuthError::Validation(_))));
 
         // create role and user profile
         let profile_role_id1 = auth.create_role_profile(role_id1, "name", StringT, SingleRequired).await.unwrap();
@@ -170,12 +432,77 @@ mod tests {
         assert_eq!(profile_user1.value, String("john doe".to_owned()));
         assert!(profile_users.contains(&profile_user1));
 
+        // read profiles of several users and roles at once and user with its profiles
+        let profile_user_map = auth.list_user_profile_by_users(&[user_id1, user_id2]).await.unwrap();
+        let profile_role_map = auth.list_role_profile_by_roles(&[role_id1, role_id2]).await.unwrap();
+        let user_profiled = auth.read_user_with_profiles(user_id1).await.unwrap();
+
+        assert_eq!(profile_user_map[&user_id1].len(), 2);
+        assert!(profile_user_map[&user_id2].is_empty());
+        assert_eq!(profile_role_map[&role_id1].len(), 2);
+        assert_eq!(user_profiled.profiles, profile_user_map[&user_id1]);
+
+        // profile filters are combined so a list of ids never widen a single user or role filter
+        let profile_user_and = operation::profile::select_user_profile(&auth.pool, None, Some(user_id1), Some(&[user_id2]), None).await.unwrap();
+        let profile_role_and = operation::profile::select_role_profile(&auth.pool, None, Some(role_id1), Some(&[role_id1, role_id2]), None).await.unwrap();
+
+        assert!(profile_user_and.is_empty());
+        assert_eq!(profile_role_and, profile_role_map[&role_id1]);
+
         // update user profile
         auth.update_user_profile(profile_user_id2, None, Some(U16(21))).await.unwrap();
         let profile_user2 = auth.read_user_profile(profile_user_id2).await.unwrap();
 
         assert_eq!(profile_user2.value, U16(21));
 
+        // add access condition on user profile compared with request parameter
+        let condition_id = auth.create_access_condition(role_id1, proc_id2, "age", ConditionOperator::GreaterEqual, &[], Some("min_age")).await.unwrap();
+        let conditions = auth.list_access_condition_by_role(role_id1).await.unwrap();
+
+        assert_eq!(conditions.len(), 1);
+        assert!(auth.check_access_with_params(user_id1, proc_id2, &[("min_age", U16(18))]).await.unwrap());
+        assert!(!auth.check_access_with_params(user_id1, proc_id2, &[("min_age", U16(30))]).await.unwrap());
+        assert!(!auth.check_access(user_id1, proc_id2).await.unwrap());
+
+        auth.delete_access_condition(condition_id).await.unwrap();
+
+        // clone role with its access and profile definition
+        let (role_id4, unmatched) = auth.clone_role(role_id1, api_id1, "operator").await.unwrap();
+        let role_clone = auth.read_role(role_id4).await.unwrap();
+        let profile_roles = auth.list_role_profile_by_role(role_id4).await.unwrap();
+
+        assert!(unmatched.is_empty());
+        assert_eq!(role_clone.name, "operator");
+        assert!(role_clone.ip_lock);
+        assert_eq!(role_clone.access_duration, 900);
+        assert_eq!(role_clone.procedures.len(), 3);
+        assert_eq!(profile_roles.len(), 2);
+
+        // clone role with deny and condition into another API which lack one of the procedures
+        let role_source = auth.create_role(Uuid::new_v4(), api_id1, "auditor", false, false, 900, 28800).await.unwrap();
+        auth.add_role_access(role_source, proc_id1).await.unwrap();
+        auth.add_role_access(role_source, proc_id2).await.unwrap();
+        auth.add_role_deny(role_source, proc_id3).await.unwrap();
+        auth.create_access_condition(role_source, proc_id1, "age", ConditionOperator::GreaterEqual, &[U16(18)], None).await.unwrap();
+        let proc_read = auth.create_procedure(Uuid::new_v4(), api_id2, "ReadData", "").await.unwrap();
+        let proc_delete = auth.create_procedure(Uuid::new_v4(), api_id2, "DeleteData", "").await.unwrap();
+        let (role_target, unmatched) = auth.clone_role(role_source, api_id2, "auditor").await.unwrap();
+        let role_cloned = auth.read_role(role_target).await.unwrap();
+        let conditions = auth.list_access_condition_by_role(role_target).await.unwrap();
+        auth.delete_role_cascade(role_target).await.unwrap();
+        auth.delete_role_cascade(role_source).await.unwrap();
+        auth.delete_procedure(proc_read).await.unwrap();
+        auth.delete_procedure(proc_delete).await.unwrap();
+
+        assert_eq!(unmatched, ["CreateData"]);
+        assert_eq!(role_cloned.api_id, api_id2);
+        assert_eq!(role_cloned.procedures, [proc_read]);
+        assert_eq!(role_cloned.denied_procedures, [proc_delete]);
+        assert_eq!(conditions.len(), 1);
+        assert_eq!(conditions[0].procedure_id, proc_read);
+        assert_eq!(conditions[0].profile, "age");
+        assert_eq!(conditions[0].values, [U16(18)]);
+
         // create new access token and refresh token
         let expire1 = DateTime::parse_from_str("2023-01-01 00:00:00 +0000", "%Y-%m-%d %H:%M:%S %z").unwrap().into();
         let expire2 = DateTime::parse_from_str("2023-01-01 12:00:00 +0000", "%Y-%m-%d %H:%M:%S %z").unwrap().into();
@@ -190,12 +517,16 @@ mod tests {
         let auth_tokens = auth.list_auth_token(&auth_token1).await.unwrap();
         let auth_token = auth_tokens.iter().filter(|x| x.auth_token == auth_token1).next().unwrap();
         let user_tokens = auth.list_token_by_user(user_id1).await.unwrap();
+        let user_token_count = auth.count_token_by_user(user_id1).await.unwrap();
+        let user_token_stream: Vec<_> = auth.stream_tokens_by_user(user_id1).try_collect().await.unwrap();
 
         assert_eq!(auth_token.user_id, user_id1);
         assert_eq!(auth_token.expire, expire1);
         assert_eq!(auth_token.ip, [192, 168, 0, 1]);
         assert_eq!(access_token.expire, expire2);
         assert_eq!(user_tokens.len(), 3);
+        assert_eq!(user_token_count, 3);
+        assert_eq!(user_token_stream.len(), 3);
 
         // update token
         let expire3 = DateTime::parse_from_str("2023-01-01 18:00:00 +0000", "%Y-%m-%d %H:%M:%S %z").unwrap().into();
@@ -212,6 +543,27 @@ mod tests {
         assert_eq!(new_auth_token.expire, expire3);
         assert_eq!(new_auth_token.ip, [192, 168, 0, 100]);
 
+        // disable and soft delete user then restore it
+        auth.disable_user(user_id2).await.unwrap();
+        let result_token = auth.create_auth_token(user_id2, expire2, &[], 1).await;
+        let users = auth.list_user_by_role(role_id3).await.unwrap();
+
+        assert!(result_token.is_err());
+        assert!(!users.iter().any(|e| e.id == user_id2));
+
+        auth.enable_user(user_id2).await.unwrap();
+        auth.soft_delete_user(user_id2).await.unwrap();
+        let user = auth.read_user(user_id2).await.unwrap();
+        let users = auth.list_user_by_role(role_id3).await.unwrap();
+
+        assert!(user.deleted_at.is_some());
+        assert!(!users.iter().any(|e| e.id == user_id2));
+
+        auth.restore_user(user_id2).await.unwrap();
+        let users = auth.list_user_by_role(role_id3).await.unwrap();
+
+        assert!(users.iter().any(|e| e.id == user_id2));
+
         // delete role and user profile
         auth.delete_user_profile(profile_user_id1).await.unwrap();
         auth.delete_role_profile(profile_role_id1).await.unwrap();
@@ -232,20 +584,37 @@ mod tests {
         assert!(try_proc.is_err());
         assert!(try_role.is_err());
         assert!(try_api.is_err());
-        assert!(try_user.is_err());
+        assert!(matches!(try_user, Err(AuthError::Referenced { by }) if by == "user_role"));
 
-        // delete user and token
+        // delete user with an active session and token
+        let access_id2 = auth.create_auth_token(user_id2, expire2, &[], 1).await.unwrap()[0].0;
         auth.remove_user_role(user_id2, role_id2).await.unwrap();
         auth.remove_user_role(user_id2, role_id3).await.unwrap();
-        auth.delete_user(user_id2).await.unwrap();
+        let revoked = auth.delete_user(user_id2).await.unwrap();
         auth.delete_token_by_user(user_id1).await.unwrap();
 
-        // check if token and user already deleted
+        // check if token, revoked session token and user already deleted
         let result_token = auth.read_access_token(access_id1).await;
+        let result_revoked = auth.read_access_token(access_id2).await;
         let result_user = auth.read_user(user_id2).await;
 
         assert!(result_token.is_err());
-        assert!(result_user.is_err());
+        assert!(matches!(result_revoked, Err(AuthError::NotFound { .. })));
+        assert!(matches!(result_user, Err(AuthError::NotFound { .. })));
+        assert_eq!(revoked, 1);
+
+        // update and delete of missing items report the missing entity
+        let try_update = auth.update_user(user_id2, Some("removed"), None, None, None).await;
+        let try_delete = auth.delete_user(user_id2).await;
+        let try_state = auth.disable_api(Uuid::new_v4()).await;
+        let try_group = auth.update_group(Uuid::new_v4(), Some("missing"), None).await;
+        let try_condition = auth.update_access_condition(-1, Some("age"), None, None, None).await;
+
+        assert!(matches!(try_update, Err(AuthError::NotFound { entity }) if entity == "user"));
+        assert!(matches!(try_delete, Err(AuthError::NotFound { entity }) if entity == "user"));
+        assert!(matches!(try_state, Err(AuthError::NotFound { entity }) if entity == "api"));
+        assert!(matches!(try_group, Err(AuthError::NotFound { entity }) if entity == "group"));
+        assert!(matches!(try_condition, Err(AuthError::NotFound { entity }) if entity == "access condition"));
 
         // delete resource API, procedure, and role
         auth.remove_user_role(user_id1, role_id3).await.unwrap();
@@ -262,6 +631,64 @@ mod tests {
         assert!(result_proc.is_err());
         assert!(result_role.is_err());
         assert!(result_api.is_err());
+
+        // provision user and its role in a transaction then rollback
+        let tx = auth.begin().await.unwrap();
+        let user_id3 = tx.create_user(Uuid::new_v4(), "operator", "operator@mail.co", "+6281234567892", password_user).await.unwrap();
+        tx.add_user_role(user_id3, role_id1).await.unwrap();
+        let user = tx.read_user(user_id3).await.unwrap();
+        let users_tx = tx.list_user_by_role(role_id1).await.unwrap();
+        let count_tx = tx.count_user(&UserFilter::default()).await.unwrap();
+        let users_stream: Vec<_> = tx.stream_users(&UserFilter::default()).try_collect().await.unwrap();
+        tx.rollback().await.unwrap();
+
+        assert_eq!(user.roles.len(), 1);
+        assert!(users_tx.iter().any(|u| u.id == user_id3));
+        assert_eq!(count_tx, auth.count_user(&UserFilter::default()).await.unwrap() + 1);
+        assert_eq!(users_stream.len() as u64, count_tx);
+        assert!(auth.read_user(user_id3).await.is_err());
+
+        // provision user in a transaction then commit
+        let tx = auth.begin().await.unwrap();
+        let user_id3 = tx.create_user(Uuid::new_v4(), "operator", "operator@mail.co", "+6281234567892", password_user).await.unwrap();
+        tx.commit().await.unwrap();
+
+        assert!(auth.read_user(user_id3).await.is_ok());
+        auth.delete_user(user_id3).await.unwrap();
+
+        // run operation functions in an external transaction then rollback
+        let mut tx = auth.pool.begin().await.unwrap();
+        operation::user::update_user(&mut *tx, user_id1, None, Some("root@mail.co"), None, None, None).await.unwrap();
+        let users = operation::user::select_user(&mut *tx, &UserFilter::default().ids(&[user_id1]), true, None).await.unwrap();
+        tx.rollback().await.unwrap();
+
+        assert_eq!(users[0].email, "root@mail.co");
+        assert_eq!(auth.read_user(user_id1).await.unwrap().email, "admin@mail.co");
+
+        // delete resource API and user with all of their dependent items and invalidate cached items
+        let auth_cache = auth.with_cache(Duration::from_secs(60), 100);
+        auth_cache.read_api(api_id1).await.unwrap();
+        auth_cache.read_role(role_id1).await.unwrap();
+        auth_cache.read_user(user_id1).await.unwrap();
+        let user_role = auth.read_user(user_id1).await.unwrap();
+        let summary_api = auth_cache.delete_api_cascade(api_id1).await.unwrap();
+        let user_touched = auth.read_user(user_id1).await.unwrap();
+        let summary_user = auth_cache.delete_user_cascade(user_id1).await.unwrap();
+
+        assert_eq!(summary_api.apis, 1);
+        assert_eq!(summary_api.procedures, 3);
+        assert_eq!(summary_api.roles, 3);
+        assert_eq!(summary_api.role_access, 7);
+        assert_eq!(summary_api.role_profiles, 3);
+        assert_eq!(summary_api.user_roles, 1);
+        assert!(user_touched.updated_at > user_role.updated_at);
+        assert_eq!(summary_user.users, 1);
+        assert_eq!(summary_user.user_profiles, 1);
+        assert!(auth.read_api(api_id1).await.is_err());
+        assert!(auth.read_user(user_id1).await.is_err());
+        assert!(auth_cache.read_api(api_id1).await.is_err());
+        assert!(auth_cache.read_role(role_id1).await.is_err());
+        assert!(auth_cache.read_user(user_id1).await.is_err());
     }
 
 }